@@ -0,0 +1,106 @@
+mod docker;
+mod ec2;
+mod hook;
+mod process;
+mod wol;
+
+use crate::config::BackendConfig;
+use crate::connection::Transfer;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io;
+
+pub use docker::{DOCKER_SOCKET, DockerBackend};
+pub use ec2::Ec2Backend;
+pub use hook::HookBackend;
+pub use process::ProcessBackend;
+pub use wol::{MacAddress, ReadyCheck, WOL_BROADCAST_ADDRESS, WOL_PORT, WakeOnLanBackend};
+
+/// The lifecycle state of the machine or process the Minecraft server runs on.
+#[derive(PartialEq, Clone, Debug)]
+pub enum BackendState {
+    Stopped,
+    // On its way up, with an estimate of how long until it's running if one is known.
+    Starting { eta: Option<Duration> },
+    Running,
+    Stopping,
+    // Can't be started at all, for the given reason.
+    Unavailable(String),
+}
+
+/// Somewhere the Minecraft server can be started on demand.
+#[async_trait]
+pub trait ServerBackend: 'static + Send + Sync {
+    async fn state(&self) -> io::Result<BackendState>;
+
+    /// Starts the server if it's stopped. Returns once it's on its way up, not once it's running.
+    async fn start(&self) -> io::Result<()>;
+
+    async fn stop(&self) -> io::Result<()>;
+
+    /// The address players can reach the server on, if it's running.
+    async fn resolve(&self) -> io::Result<Option<Transfer>>;
+
+    /// Called when the receptionist exits. Servers that can't outlive it are stopped here, and
+    /// this returns once they have.
+    async fn shutdown(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sets up the backend chosen in the config. Backends that are slow to ask for their state
+/// reuse it for `status_cache_ttl`.
+pub async fn from_config(
+    config: BackendConfig,
+    port: u16,
+    status_cache_ttl: Duration,
+) -> Arc<dyn ServerBackend> {
+    match config {
+        BackendConfig::Ec2 { instance_name } => {
+            Arc::new(Ec2Backend::new(instance_name, port).await)
+        }
+        BackendConfig::Process {
+            command,
+            working_directory,
+            hostname,
+        } => Arc::new(ProcessBackend::new(
+            command,
+            working_directory,
+            hostname,
+            port,
+        )),
+        BackendConfig::Hook {
+            start_command,
+            status_command,
+            stop_command,
+            hostname,
+        } => Arc::new(HookBackend::new(
+            start_command,
+            status_command,
+            stop_command,
+            hostname,
+            port,
+            status_cache_ttl,
+        )),
+        BackendConfig::Docker {
+            container,
+            socket,
+            hostname,
+        } => Arc::new(DockerBackend::new(container, socket, hostname, port)),
+        BackendConfig::Wol {
+            mac,
+            broadcast_address,
+            wol_port,
+            hostname,
+            ready_check,
+        } => Arc::new(WakeOnLanBackend::new(
+            mac,
+            broadcast_address,
+            wol_port,
+            hostname,
+            port,
+            ready_check,
+        )),
+    }
+}
@@ -0,0 +1,376 @@
+use crate::backend::{BackendState, ServerBackend};
+use crate::connection::Transfer;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+pub const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// A container managed through the Docker Engine API, with the server's port published on this
+/// machine.
+pub struct DockerBackend {
+    container: String,
+    socket: PathBuf,
+    hostname: String,
+    port: u16,
+}
+
+// The parts of GET /containers/{name}/json used here.
+#[derive(Deserialize, Debug)]
+struct ContainerInspect {
+    #[serde(rename = "State")]
+    state: ContainerState,
+    #[serde(rename = "NetworkSettings")]
+    network_settings: NetworkSettings,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContainerState {
+    // created, running, paused, restarting, removing, exited or dead
+    #[serde(rename = "Status")]
+    status: String,
+    // Only present if the image or container defines a health check.
+    #[serde(rename = "Health")]
+    health: Option<ContainerHealth>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContainerHealth {
+    #[serde(rename = "Status")]
+    status: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkSettings {
+    // e.g. "25565/tcp" to the host ports it's published on. Ports that aren't published map
+    // to null.
+    #[serde(rename = "Ports", default)]
+    ports: HashMap<String, Option<Vec<PortBinding>>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PortBinding {
+    #[serde(rename = "HostPort")]
+    host_port: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    message: String,
+}
+
+impl DockerBackend {
+    pub fn new(container: String, socket: PathBuf, hostname: String, port: u16) -> DockerBackend {
+        DockerBackend {
+            container,
+            socket,
+            hostname,
+            port,
+        }
+    }
+
+    // The backend state a container is in. A running container with a health check isn't ready
+    // for players until the check passes.
+    fn backend_state(state: &ContainerState) -> BackendState {
+        match state.status.as_str() {
+            "created" | "exited" => BackendState::Stopped,
+            "running" => match &state.health {
+                Some(health) if health.status == "starting" => BackendState::Starting { eta: None },
+                _ => BackendState::Running,
+            },
+            "restarting" => BackendState::Starting { eta: None },
+            "removing" => BackendState::Stopping,
+            status => BackendState::Unavailable(status.to_string()),
+        }
+    }
+
+    async fn inspect(&self) -> io::Result<ContainerInspect> {
+        let path = format!("/containers/{}/json", self.container);
+        let body = self.request("GET", &path).await?;
+        serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Sends a request without a body and returns the response body, or the error the engine
+    // responded with.
+    async fn request(&self, method: &str, path: &str) -> io::Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.socket).await?;
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: docker\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            method, path
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        let (status, body) = parse_response(&response)?;
+        match status {
+            // 304 means it was already started or stopped
+            200..=299 | 304 => Ok(body),
+            _ => {
+                let message = serde_json::from_slice::<ErrorResponse>(&body)
+                    .map(|error| error.message)
+                    .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned());
+                let kind = match status {
+                    404 => io::ErrorKind::NotFound,
+                    _ => io::ErrorKind::Other,
+                };
+                Err(io::Error::new(
+                    kind,
+                    format!("{} {} failed with {}: {}", method, path, status, message),
+                ))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ServerBackend for DockerBackend {
+    async fn state(&self) -> io::Result<BackendState> {
+        Ok(DockerBackend::backend_state(&self.inspect().await?.state))
+    }
+
+    async fn start(&self) -> io::Result<()> {
+        println!("starting container {}...", self.container);
+        let path = format!("/containers/{}/start", self.container);
+        self.request("POST", &path).await?;
+        Ok(())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        println!("stopping container {}...", self.container);
+        let path = format!("/containers/{}/stop", self.container);
+        self.request("POST", &path).await?;
+        Ok(())
+    }
+
+    async fn resolve(&self) -> io::Result<Option<Transfer>> {
+        let inspect = self.inspect().await?;
+        if DockerBackend::backend_state(&inspect.state) != BackendState::Running {
+            return Ok(None);
+        }
+        let container_port = format!("{}/tcp", self.port);
+        let host_port = inspect
+            .network_settings
+            .ports
+            .get(&container_port)
+            .and_then(|bindings| bindings.as_ref()?.first())
+            .and_then(|binding| binding.host_port.parse().ok())
+            .ok_or_else(|| {
+                io::Error::other(format!(
+                    "container {} doesn't publish port {}",
+                    self.container, container_port
+                ))
+            })?;
+        Ok(Some(Transfer {
+            hostname: self.hostname.clone(),
+            port: host_port,
+        }))
+    }
+}
+
+// Splits an HTTP/1.1 response into its status code and body.
+fn parse_response(response: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response");
+    let head_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(invalid)?;
+    let head = std::str::from_utf8(&response[..head_end]).map_err(|_| invalid())?;
+    let body = &response[head_end + 4..];
+
+    let mut lines = head.split("\r\n");
+    // e.g. "HTTP/1.1 204 No Content"
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split(' ').nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(invalid)?;
+    let chunked = lines.any(|header| {
+        let Some((name, value)) = header.split_once(':') else {
+            return false;
+        };
+        name.trim().eq_ignore_ascii_case("transfer-encoding")
+            && value.trim().eq_ignore_ascii_case("chunked")
+    });
+
+    if !chunked {
+        return Ok((status, body.to_vec()));
+    }
+    // Each chunk is its length in hex, then the data, each followed by a CRLF. A zero-length
+    // chunk ends the body.
+    let mut decoded = Vec::new();
+    let mut rest = body;
+    loop {
+        let line_end = rest
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(invalid)?;
+        let size = std::str::from_utf8(&rest[..line_end]).map_err(|_| invalid())?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+        if size == 0 {
+            return Ok((status, decoded));
+        }
+        let data = rest
+            .get(line_end + 2..line_end + 2 + size)
+            .ok_or_else(invalid)?;
+        decoded.extend_from_slice(data);
+        rest = rest.get(line_end + 2 + size + 2..).ok_or_else(invalid)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::UnixListener;
+
+    // Stands in for the Docker Engine, serving a single container named "minecraft" whose
+    // status changes as it's started and stopped, along with its health if it has a check.
+    fn stub_engine(
+        name: &str,
+        status: Arc<Mutex<&'static str>>,
+        health: Arc<Mutex<Option<&'static str>>>,
+    ) -> PathBuf {
+        let socket =
+            std::env::temp_dir().join(format!("receptionist-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let len = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..len]);
+                }
+                let request_line = String::from_utf8(request).unwrap();
+                let request_line = request_line.split("\r\n").next().unwrap().to_string();
+
+                let response = match request_line.as_str() {
+                    "GET /containers/minecraft/json HTTP/1.1" => {
+                        let health = match *health.lock().unwrap() {
+                            Some(health) => format!(r#", "Health": {{"Status": "{}"}}"#, health),
+                            None => String::new(),
+                        };
+                        let body = format!(
+                            r#"{{"State": {{"Status": "{}"{}}}, "NetworkSettings": {{"Ports": {{"25565/tcp": [{{"HostIp": "0.0.0.0", "HostPort": "32768"}}], "25575/tcp": null}}}}}}"#,
+                            status.lock().unwrap(),
+                            health
+                        );
+                        // the engine streams larger responses in chunks
+                        let (first, second) = body.split_at(body.len() / 2);
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                            first.len(),
+                            first,
+                            second.len(),
+                            second
+                        )
+                    }
+                    "POST /containers/minecraft/start HTTP/1.1" => {
+                        *status.lock().unwrap() = "running";
+                        "HTTP/1.1 204 No Content\r\n\r\n".to_string()
+                    }
+                    "POST /containers/minecraft/stop HTTP/1.1" => {
+                        *status.lock().unwrap() = "exited";
+                        "HTTP/1.1 204 No Content\r\n\r\n".to_string()
+                    }
+                    _ => {
+                        let body = r#"{"message": "No such container"}"#;
+                        format!(
+                            "HTTP/1.1 404 Not Found\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        socket
+    }
+
+    #[tokio::test]
+    async fn start_and_stop() -> Result<(), io::Error> {
+        let status = Arc::new(Mutex::new("created"));
+        let socket = stub_engine("docker", status.clone(), Arc::new(Mutex::new(None)));
+        let backend = DockerBackend::new(
+            "minecraft".to_string(),
+            socket.clone(),
+            "localhost".to_string(),
+            25565,
+        );
+
+        assert_eq!(backend.state().await?, BackendState::Stopped);
+        assert!(backend.resolve().await?.is_none());
+
+        backend.start().await?;
+        assert_eq!(backend.state().await?, BackendState::Running);
+        let transfer = backend.resolve().await?.unwrap();
+        assert_eq!(
+            (transfer.hostname.as_str(), transfer.port),
+            ("localhost", 32768)
+        );
+
+        backend.stop().await?;
+        assert_eq!(backend.state().await?, BackendState::Stopped);
+
+        *status.lock().unwrap() = "dead";
+        assert_eq!(
+            backend.state().await?,
+            BackendState::Unavailable("dead".to_string())
+        );
+
+        let missing = DockerBackend::new(
+            "vanilla".to_string(),
+            socket.clone(),
+            "localhost".to_string(),
+            25565,
+        );
+        let error = missing.state().await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("No such container"));
+
+        std::fs::remove_file(socket)
+    }
+
+    #[tokio::test]
+    async fn waits_for_health_check() -> Result<(), io::Error> {
+        let health = Arc::new(Mutex::new(Some("starting")));
+        let socket = stub_engine(
+            "docker-health",
+            Arc::new(Mutex::new("running")),
+            health.clone(),
+        );
+        let backend = DockerBackend::new(
+            "minecraft".to_string(),
+            socket.clone(),
+            "localhost".to_string(),
+            25565,
+        );
+
+        assert_eq!(backend.state().await?, BackendState::Starting { eta: None });
+        assert!(backend.resolve().await?.is_none());
+
+        *health.lock().unwrap() = Some("healthy");
+        assert_eq!(backend.state().await?, BackendState::Running);
+        assert!(backend.resolve().await?.is_some());
+
+        std::fs::remove_file(socket)
+    }
+
+    #[test]
+    fn content_length_response() -> Result<(), io::Error> {
+        let (status, body) =
+            parse_response(b"HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n")?;
+        assert_eq!((status, body.as_slice()), (304, b"".as_slice()));
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+        Ok(())
+    }
+}
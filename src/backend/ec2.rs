@@ -0,0 +1,146 @@
+use crate::backend::{BackendState, ServerBackend};
+use crate::connection::Transfer;
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::types::{Filter, Instance, InstanceStateName};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io;
+
+// Roughly how long it takes from launching the instance to the server accepting players.
+const EXPECTED_BOOT_TIME: u64 = 2 * 60;
+
+/// An EC2 instance found by its Name tag, with the server listening on its public IP.
+pub struct Ec2Backend {
+    instance_name: String,
+    port: u16,
+    ec2: aws_sdk_ec2::Client,
+}
+
+impl Ec2Backend {
+    pub async fn new(instance_name: String, port: u16) -> Ec2Backend {
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .load()
+            .await;
+        let ec2 = aws_sdk_ec2::Client::new(&config);
+
+        Ec2Backend {
+            instance_name,
+            port,
+            ec2,
+        }
+    }
+
+    async fn describe_instance(&self) -> io::Result<Instance> {
+        let filter = Filter::builder()
+            .name("tag:Name")
+            .values(&self.instance_name)
+            .build();
+        let description = self
+            .ec2
+            .describe_instances()
+            .filters(filter)
+            .send()
+            .await
+            .map_err(ec2_error)?;
+        description
+            .reservations()
+            .iter()
+            .flat_map(|reservation| reservation.instances())
+            .next()
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no instance named {}", self.instance_name),
+                )
+            })
+    }
+
+    fn get_public_ip(instance: &Instance) -> Option<String> {
+        instance
+            .network_interfaces()
+            .iter()
+            .filter_map(|network_interface| network_interface.association())
+            .find_map(|association| association.public_ip())
+            .map(|public_ip| public_ip.to_string())
+    }
+
+    fn instance_id(instance: &Instance) -> io::Result<&str> {
+        instance
+            .instance_id()
+            .ok_or_else(|| io::Error::other("instance has no ID"))
+    }
+
+    // Estimates how long until a pending instance is running, based on when it was launched.
+    fn boot_eta(instance: &Instance) -> Option<Duration> {
+        let launch_time = instance.launch_time()?.secs();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        let elapsed = (now - launch_time).max(0) as u64;
+        // once it's overdue, there's no telling how much longer it'll be
+        if elapsed >= EXPECTED_BOOT_TIME {
+            return None;
+        }
+        Some(Duration::from_secs(EXPECTED_BOOT_TIME - elapsed))
+    }
+}
+
+#[async_trait]
+impl ServerBackend for Ec2Backend {
+    async fn state(&self) -> io::Result<BackendState> {
+        let instance = self.describe_instance().await?;
+        let state = instance
+            .state()
+            .and_then(|state| state.name())
+            .ok_or_else(|| io::Error::other("instance has no state"))?;
+        Ok(match state {
+            InstanceStateName::Stopped => BackendState::Stopped,
+            InstanceStateName::Pending => BackendState::Starting {
+                eta: Ec2Backend::boot_eta(&instance),
+            },
+            InstanceStateName::Running => BackendState::Running,
+            InstanceStateName::Stopping => BackendState::Stopping,
+            state => BackendState::Unavailable(state.to_string()),
+        })
+    }
+
+    async fn start(&self) -> io::Result<()> {
+        let instance = self.describe_instance().await?;
+        let instance_id = Ec2Backend::instance_id(&instance)?;
+        println!("launching {}...", instance_id);
+        self.ec2
+            .start_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await
+            .map_err(ec2_error)?;
+        Ok(())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        let instance = self.describe_instance().await?;
+        let instance_id = Ec2Backend::instance_id(&instance)?;
+        println!("stopping {}...", instance_id);
+        self.ec2
+            .stop_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await
+            .map_err(ec2_error)?;
+        Ok(())
+    }
+
+    async fn resolve(&self) -> io::Result<Option<Transfer>> {
+        let instance = self.describe_instance().await?;
+        if instance.state().and_then(|state| state.name()) != Some(&InstanceStateName::Running) {
+            return Ok(None);
+        }
+        Ok(Ec2Backend::get_public_ip(&instance).map(|public_ip| Transfer {
+            hostname: public_ip,
+            port: self.port,
+        }))
+    }
+}
+
+fn ec2_error(e: impl Into<aws_sdk_ec2::Error>) -> io::Error {
+    io::Error::other(e.into())
+}
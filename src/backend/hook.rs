@@ -0,0 +1,251 @@
+use crate::backend::{BackendState, ServerBackend};
+use crate::connection::Transfer;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::process::{Output, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+// Hooks talk to other machines, but shouldn't hold up a player for longer than this.
+const HOOK_TIMEOUT: u64 = 30;
+
+// The status command runs for every server list ping, so it gets much less time.
+const STATUS_TIMEOUT: u64 = 5;
+
+/// A server driven by user-supplied shell commands, for infrastructure the receptionist has no
+/// built-in support for.
+pub struct HookBackend {
+    start_command: String,
+    status_command: String,
+    stop_command: String,
+    hostname: Option<String>,
+    port: u16,
+    // The last status and when it was fetched, so that every ping doesn't run the command.
+    status_cache: Mutex<Option<(Instant, HookStatus)>>,
+    status_cache_ttl: Duration,
+}
+
+/// What a status command can print instead of relying on its exit code, e.g.
+/// `{"state": "running", "hostname": "10.0.0.5"}`.
+#[derive(Deserialize, Clone, Debug)]
+struct HookStatus {
+    // One of stopped, starting, running or stopping. Anything else means the server can't be
+    // started, and is shown to players.
+    state: String,
+    // Seconds until a starting server is expected to be running.
+    eta: Option<u64>,
+    hostname: Option<String>,
+    port: Option<u16>,
+}
+
+impl HookBackend {
+    pub fn new(
+        start_command: String,
+        status_command: String,
+        stop_command: String,
+        hostname: Option<String>,
+        port: u16,
+        status_cache_ttl: Duration,
+    ) -> HookBackend {
+        HookBackend {
+            start_command,
+            status_command,
+            stop_command,
+            hostname,
+            port,
+            status_cache: Mutex::new(None),
+            status_cache_ttl,
+        }
+    }
+
+    async fn run(command: &str, limit: Duration) -> io::Result<Output> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        timeout(limit, output).await.map_err(|_| {
+            io::Error::new(io::ErrorKind::TimedOut, format!("{} timed out", command))
+        })?
+    }
+
+    async fn run_checked(command: &str) -> io::Result<()> {
+        let output = HookBackend::run(command, Duration::from_secs(HOOK_TIMEOUT)).await?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "{} failed with {}: {}",
+                command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    // Without JSON on stdout, a status command that succeeds means the server is running, like
+    // `systemctl is-active`.
+    async fn status(&self) -> io::Result<HookStatus> {
+        if let Some((fetched, status)) = &*self.status_cache.lock().unwrap()
+            && fetched.elapsed() < self.status_cache_ttl
+        {
+            return Ok(status.clone());
+        }
+
+        let output =
+            HookBackend::run(&self.status_command, Duration::from_secs(STATUS_TIMEOUT)).await?;
+        let status = serde_json::from_slice::<HookStatus>(&output.stdout).unwrap_or_else(|_| {
+            let state = if output.status.success() {
+                "running"
+            } else {
+                "stopped"
+            };
+            HookStatus {
+                state: state.to_string(),
+                eta: None,
+                hostname: None,
+                port: None,
+            }
+        });
+        *self.status_cache.lock().unwrap() = Some((Instant::now(), status.clone()));
+        Ok(status)
+    }
+}
+
+#[async_trait]
+impl ServerBackend for HookBackend {
+    async fn state(&self) -> io::Result<BackendState> {
+        let status = self.status().await?;
+        Ok(match status.state.as_str() {
+            "stopped" => BackendState::Stopped,
+            "starting" => BackendState::Starting {
+                eta: status.eta.map(Duration::from_secs),
+            },
+            "running" => BackendState::Running,
+            "stopping" => BackendState::Stopping,
+            _ => BackendState::Unavailable(status.state),
+        })
+    }
+
+    async fn start(&self) -> io::Result<()> {
+        println!("running start command: {}", self.start_command);
+        let result = HookBackend::run_checked(&self.start_command).await;
+        // whatever happened, the cached status is out of date
+        *self.status_cache.lock().unwrap() = None;
+        result
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        println!("running stop command: {}", self.stop_command);
+        let result = HookBackend::run_checked(&self.stop_command).await;
+        *self.status_cache.lock().unwrap() = None;
+        result
+    }
+
+    async fn resolve(&self) -> io::Result<Option<Transfer>> {
+        let status = self.status().await?;
+        if status.state != "running" {
+            return Ok(None);
+        }
+        let hostname = status
+            .hostname
+            .or_else(|| self.hostname.clone())
+            .ok_or_else(|| {
+                io::Error::other("no hostname configured or printed by the status command")
+            })?;
+        Ok(Some(Transfer {
+            hostname,
+            port: status.port.unwrap_or(self.port),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(status_command: &str, hostname: Option<&str>) -> HookBackend {
+        HookBackend::new(
+            "true".to_string(),
+            status_command.to_string(),
+            "exit 1".to_string(),
+            hostname.map(|hostname| hostname.to_string()),
+            25565,
+            Duration::ZERO,
+        )
+    }
+
+    #[tokio::test]
+    async fn exit_code_status() -> Result<(), io::Error> {
+        let running = backend("echo active", Some("10.0.0.5"));
+        assert_eq!(running.state().await?, BackendState::Running);
+        let transfer = running.resolve().await?.unwrap();
+        assert_eq!(
+            (transfer.hostname.as_str(), transfer.port),
+            ("10.0.0.5", 25565)
+        );
+
+        let stopped = backend("echo inactive; exit 3", Some("10.0.0.5"));
+        assert_eq!(stopped.state().await?, BackendState::Stopped);
+        assert!(stopped.resolve().await?.is_none());
+
+        // running, but with nowhere to send players
+        assert!(backend("true", None).resolve().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_status() -> Result<(), io::Error> {
+        let starting = backend(r#"echo '{"state": "starting", "eta": 30}'"#, None);
+        assert_eq!(
+            starting.state().await?,
+            BackendState::Starting {
+                eta: Some(Duration::from_secs(30))
+            }
+        );
+
+        let running = backend(
+            r#"echo '{"state": "running", "hostname": "192.168.1.20", "port": 25566}'"#,
+            Some("10.0.0.5"),
+        );
+        let transfer = running.resolve().await?.unwrap();
+        assert_eq!(
+            (transfer.hostname.as_str(), transfer.port),
+            ("192.168.1.20", 25566)
+        );
+
+        let unavailable = backend(r#"echo '{"state": "migrating"}'"#, None);
+        assert_eq!(
+            unavailable.state().await?,
+            BackendState::Unavailable("migrating".to_string())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cached_status() -> Result<(), io::Error> {
+        let calls = std::env::temp_dir().join(format!("receptionist-hook-{}", std::process::id()));
+        let mut backend = backend(&format!("echo >> {}", calls.display()), None);
+        backend.status_cache_ttl = Duration::from_secs(60);
+
+        backend.state().await?;
+        backend.state().await?;
+        assert_eq!(std::fs::read_to_string(&calls)?.lines().count(), 1);
+
+        // starting the server makes the cached status stale
+        backend.start().await?;
+        backend.state().await?;
+        assert_eq!(std::fs::read_to_string(&calls)?.lines().count(), 2);
+        std::fs::remove_file(calls)
+    }
+
+    #[tokio::test]
+    async fn failing_commands() {
+        let backend = backend("true", None);
+        assert!(backend.start().await.is_ok());
+        assert!(backend.stop().await.is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::backend::{BackendState, ServerBackend};
+use crate::connection::Transfer;
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+// What the vanilla server logs once it's listening, e.g.
+// "[Server thread/INFO]: Done (5.123s)! For help, type "help"".
+const DONE_PREFIX: &str = "Done (";
+const DONE_SUFFIX: &str = "For help";
+
+// How long the server gets to save the world and exit when the receptionist shuts down, before
+// it's killed.
+const SHUTDOWN_TIMEOUT: u64 = 60;
+
+/// A server run as a child process of the receptionist, on the same machine.
+pub struct ProcessBackend {
+    command: Vec<String>,
+    working_directory: PathBuf,
+    hostname: String,
+    port: u16,
+    process: Arc<Mutex<Process>>,
+    stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+    // The task that owns the child process, which finishes when it exits.
+    waiter: Mutex<Option<JoinHandle<()>>>,
+    shutdown_timeout: Duration,
+}
+
+#[derive(Default)]
+struct Process {
+    state: ProcessState,
+    // How long the last start took, to estimate how long the next will.
+    last_boot: Option<Duration>,
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+enum ProcessState {
+    #[default]
+    Stopped,
+    Starting {
+        since: Instant,
+    },
+    Running,
+    Stopping,
+}
+
+impl ProcessBackend {
+    pub fn new(
+        command: Vec<String>,
+        working_directory: PathBuf,
+        hostname: String,
+        port: u16,
+    ) -> ProcessBackend {
+        ProcessBackend {
+            command,
+            working_directory,
+            hostname,
+            port,
+            process: Arc::new(Mutex::new(Process::default())),
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
+            waiter: Mutex::new(None),
+            shutdown_timeout: Duration::from_secs(SHUTDOWN_TIMEOUT),
+        }
+    }
+}
+
+#[async_trait]
+impl ServerBackend for ProcessBackend {
+    async fn state(&self) -> io::Result<BackendState> {
+        let process = self.process.lock().unwrap();
+        Ok(match process.state {
+            ProcessState::Stopped => BackendState::Stopped,
+            ProcessState::Starting { since } => BackendState::Starting {
+                eta: process
+                    .last_boot
+                    .map(|last_boot| last_boot.saturating_sub(since.elapsed())),
+            },
+            ProcessState::Running => BackendState::Running,
+            ProcessState::Stopping => BackendState::Stopping,
+        })
+    }
+
+    async fn start(&self) -> io::Result<()> {
+        let mut stdin = self.stdin.lock().await;
+        {
+            let mut process = self.process.lock().unwrap();
+            if process.state != ProcessState::Stopped {
+                return Ok(());
+            }
+            process.state = ProcessState::Starting {
+                since: Instant::now(),
+            };
+        }
+
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| io::Error::other("no server command configured"))?;
+        println!("launching {}...", self.command.join(" "));
+        let spawned = Command::new(program)
+            .args(args)
+            .current_dir(&self.working_directory)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // only if it ignores stop, see shutdown
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                self.process.lock().unwrap().state = ProcessState::Stopped;
+                return Err(e);
+            }
+        };
+        *stdin = child.stdin.take();
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        // Pass the server's output through, watching for the line that says it's listening.
+        let process = self.process.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                println!("[server] {}", line);
+                if line.contains(DONE_PREFIX) && line.contains(DONE_SUFFIX) {
+                    let mut process = process.lock().unwrap();
+                    if let ProcessState::Starting { since } = process.state {
+                        process.state = ProcessState::Running;
+                        process.last_boot = Some(since.elapsed());
+                    }
+                }
+            }
+        });
+
+        let process = self.process.clone();
+        let stdin = self.stdin.clone();
+        let waiter = tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) => println!("server exited with {}", status),
+                Err(e) => eprintln!("unable to wait for server: {}", e),
+            }
+            *stdin.lock().await = None;
+            process.lock().unwrap().state = ProcessState::Stopped;
+        });
+        *self.waiter.lock().unwrap() = Some(waiter);
+
+        Ok(())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        let mut stdin = self.stdin.lock().await;
+        let Some(stdin) = stdin.as_mut() else {
+            return Ok(());
+        };
+        println!("stopping server...");
+        self.process.lock().unwrap().state = ProcessState::Stopping;
+        // The server saves the world and exits on its own.
+        stdin.write_all(b"stop\n").await?;
+        stdin.flush().await
+    }
+
+    async fn resolve(&self) -> io::Result<Option<Transfer>> {
+        if self.process.lock().unwrap().state != ProcessState::Running {
+            return Ok(None);
+        }
+        Ok(Some(Transfer {
+            hostname: self.hostname.clone(),
+            port: self.port,
+        }))
+    }
+
+    // The server is a child of the receptionist, so it has to go too.
+    async fn shutdown(&self) -> io::Result<()> {
+        let Some(mut waiter) = self.waiter.lock().unwrap().take() else {
+            return Ok(());
+        };
+        if let Err(e) = self.stop().await {
+            eprintln!("unable to stop server: {}", e);
+        }
+        if timeout(self.shutdown_timeout, &mut waiter).await.is_err() {
+            eprintln!(
+                "server didn't stop within {}s, killing it",
+                self.shutdown_timeout.as_secs()
+            );
+            // Dropping the child along with the task kills it.
+            waiter.abort();
+            let _ = waiter.await;
+            self.process.lock().unwrap().state = ProcessState::Stopped;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tokio::time::sleep;
+
+    // Stands in for the server: logs like it does, then runs until told to stop.
+    const STUB_SERVER: &str = r#"
+echo "[Server thread/INFO]: Starting minecraft server version 1.21.10"
+sleep 0.2
+echo "[Server thread/INFO]: Done (0.200s)! For help, type \"help\""
+while read line; do
+    if [ "$line" = "stop" ]; then
+        echo "[Server thread/INFO]: Stopping server"
+        exit 0
+    fi
+done
+"#;
+
+    fn stub_backend(name: &str, script: &str) -> ProcessBackend {
+        let working_directory =
+            std::env::temp_dir().join(format!("receptionist-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&working_directory).unwrap();
+        fs::write(working_directory.join("server.sh"), script).unwrap();
+        ProcessBackend::new(
+            vec!["sh".to_string(), "server.sh".to_string()],
+            working_directory,
+            "localhost".to_string(),
+            25565,
+        )
+    }
+
+    async fn wait_for(backend: &ProcessBackend, state: BackendState) {
+        timeout(Duration::from_secs(5), async {
+            while backend.state().await.unwrap() != state {
+                sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("server never reached {:?}", state));
+    }
+
+    #[tokio::test]
+    async fn start_and_stop() -> Result<(), io::Error> {
+        let backend = stub_backend("start-and-stop", STUB_SERVER);
+        assert_eq!(backend.state().await?, BackendState::Stopped);
+
+        backend.start().await?;
+        assert_eq!(backend.state().await?, BackendState::Starting { eta: None });
+        assert!(backend.resolve().await?.is_none());
+
+        wait_for(&backend, BackendState::Running).await;
+        let transfer = backend.resolve().await?.unwrap();
+        assert_eq!(
+            (transfer.hostname.as_str(), transfer.port),
+            ("localhost", 25565)
+        );
+
+        backend.stop().await?;
+        wait_for(&backend, BackendState::Stopped).await;
+
+        // the next start is estimated from the last one
+        backend.start().await?;
+        assert!(matches!(
+            backend.state().await?,
+            BackendState::Starting { eta: Some(_) }
+        ));
+        backend.stop().await?;
+        wait_for(&backend, BackendState::Stopped).await;
+        fs::remove_dir_all(&backend.working_directory)
+    }
+
+    #[tokio::test]
+    async fn shutdown() -> Result<(), io::Error> {
+        let backend = stub_backend("shutdown", STUB_SERVER);
+        backend.start().await?;
+        wait_for(&backend, BackendState::Running).await;
+        backend.shutdown().await?;
+        assert_eq!(backend.state().await?, BackendState::Stopped);
+
+        // a server that ignores stop is killed in the end
+        let mut stubborn = stub_backend("stubborn", "while true; do sleep 0.1; done\n");
+        stubborn.shutdown_timeout = Duration::from_millis(200);
+        stubborn.start().await?;
+        stubborn.shutdown().await?;
+        assert_eq!(stubborn.state().await?, BackendState::Stopped);
+
+        fs::remove_dir_all(&backend.working_directory)?;
+        fs::remove_dir_all(&stubborn.working_directory)
+    }
+
+    #[tokio::test]
+    async fn crash_while_starting() -> Result<(), io::Error> {
+        let backend = stub_backend("crash", "echo \"Failed to load eula.txt\"\nexit 1\n");
+        backend.start().await?;
+        wait_for(&backend, BackendState::Stopped).await;
+        assert!(backend.resolve().await?.is_none());
+        fs::remove_dir_all(&backend.working_directory)
+    }
+}
@@ -0,0 +1,236 @@
+use crate::backend::{BackendState, ServerBackend};
+use crate::connection::{Transfer, ping_status};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+pub const WOL_BROADCAST_ADDRESS: &str = "255.255.255.255";
+pub const WOL_PORT: u16 = 9;
+
+// How long to wait on a machine that may be asleep before deciding it isn't answering. Asleep,
+// it never will, so this keeps the server list responsive.
+const PROBE_TIMEOUT: u64 = 1;
+
+// How long after waking the machine it counts as starting rather than asleep. After this, it
+// presumably didn't wake up, and joining sends another magic packet.
+const WAKE_TIMEOUT: u64 = 5 * 60;
+
+/// A MAC address, written as six hex bytes separated by colons or dashes.
+#[derive(Deserialize, PartialEq, Copy, Clone, Debug)]
+#[serde(try_from = "String")]
+pub struct MacAddress([u8; 6]);
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid MAC address: {}", s);
+        let bytes = s
+            .split([':', '-'])
+            .map(|byte| match byte.len() {
+                2 => u8::from_str_radix(byte, 16).map_err(|_| invalid()),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        Ok(MacAddress(bytes.try_into().map_err(|_| invalid())?))
+    }
+}
+
+impl TryFrom<String> for MacAddress {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+impl MacAddress {
+    // Six 0xFF bytes followed by the MAC address sixteen times.
+    fn magic_packet(&self) -> Vec<u8> {
+        let mut packet = vec![0xFF; 6];
+        for _ in 0..16 {
+            packet.extend_from_slice(&self.0);
+        }
+        packet
+    }
+}
+
+/// How to tell that the woken machine is ready for players.
+#[derive(Deserialize, PartialEq, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadyCheck {
+    // Something accepts connections on the server's port.
+    Tcp,
+    // The server answers a status ping, so it has finished starting.
+    #[default]
+    Status,
+}
+
+/// A physical machine that sleeps when idle, woken with a Wake-on-LAN magic packet. The
+/// server is expected to start on its own once the machine is awake.
+pub struct WakeOnLanBackend {
+    mac: MacAddress,
+    broadcast_address: String,
+    wol_port: u16,
+    hostname: String,
+    port: u16,
+    ready_check: ReadyCheck,
+    // When the last magic packet was sent.
+    woken_at: Mutex<Option<Instant>>,
+}
+
+impl WakeOnLanBackend {
+    pub fn new(
+        mac: MacAddress,
+        broadcast_address: String,
+        wol_port: u16,
+        hostname: String,
+        port: u16,
+        ready_check: ReadyCheck,
+    ) -> WakeOnLanBackend {
+        WakeOnLanBackend {
+            mac,
+            broadcast_address,
+            wol_port,
+            hostname,
+            port,
+            ready_check,
+            woken_at: Mutex::new(None),
+        }
+    }
+
+    async fn is_ready(&self) -> bool {
+        let probe = async {
+            match self.ready_check {
+                ReadyCheck::Tcp => TcpStream::connect((self.hostname.as_str(), self.port))
+                    .await
+                    .is_ok(),
+                ReadyCheck::Status => ping_status(&self.hostname, self.port).await.is_ok(),
+            }
+        };
+        timeout(Duration::from_secs(PROBE_TIMEOUT), probe)
+            .await
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl ServerBackend for WakeOnLanBackend {
+    async fn state(&self) -> io::Result<BackendState> {
+        if self.is_ready().await {
+            return Ok(BackendState::Running);
+        }
+        let woken_at = *self.woken_at.lock().unwrap();
+        Ok(match woken_at {
+            Some(woken_at) if woken_at.elapsed() < Duration::from_secs(WAKE_TIMEOUT) => {
+                BackendState::Starting { eta: None }
+            }
+            _ => BackendState::Stopped,
+        })
+    }
+
+    async fn start(&self) -> io::Result<()> {
+        println!("waking {}...", self.mac);
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.set_broadcast(true)?;
+        socket
+            .send_to(
+                &self.mac.magic_packet(),
+                (self.broadcast_address.as_str(), self.wol_port),
+            )
+            .await?;
+        *self.woken_at.lock().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+
+    async fn stop(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Wake-on-LAN can't put the server to sleep",
+        ))
+    }
+
+    async fn resolve(&self) -> io::Result<Option<Transfer>> {
+        if !self.is_ready().await {
+            return Ok(None);
+        }
+        Ok(Some(Transfer {
+            hostname: self.hostname.clone(),
+            port: self.port,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn parse_mac_address() {
+        let mac: MacAddress = "00:1A:2b:3c:4d:5e".parse().unwrap();
+        assert_eq!(mac, MacAddress([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]));
+        assert_eq!(mac.to_string(), "00:1a:2b:3c:4d:5e");
+        assert_eq!("00-1a-2b-3c-4d-5e".parse::<MacAddress>(), Ok(mac));
+
+        assert!("00:1a:2b:3c:4d".parse::<MacAddress>().is_err());
+        assert!("00:1a:2b:3c:4d:5e:6f".parse::<MacAddress>().is_err());
+        assert!("001a:2b:3c:4d:5e".parse::<MacAddress>().is_err());
+        assert!("00:1a:2b:3c:4d:zz".parse::<MacAddress>().is_err());
+    }
+
+    #[tokio::test]
+    async fn wake_until_ready() -> Result<(), io::Error> {
+        // something to receive the magic packet, in place of the sleeping machine's network card
+        let receiver = UdpSocket::bind("127.0.0.1:0").await?;
+        // a free port for the server to listen on later
+        let port = TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+
+        let mac = MacAddress([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]);
+        let backend = WakeOnLanBackend::new(
+            mac,
+            "127.0.0.1".to_string(),
+            receiver.local_addr()?.port(),
+            "127.0.0.1".to_string(),
+            port,
+            ReadyCheck::Tcp,
+        );
+        assert_eq!(backend.state().await?, BackendState::Stopped);
+
+        backend.start().await?;
+        let mut packet = [0u8; 256];
+        let len = receiver.recv(&mut packet).await?;
+        assert_eq!(&packet[..len], mac.magic_packet().as_slice());
+        assert_eq!(len, 102);
+        assert_eq!(&packet[6..12], &mac.0);
+        assert_eq!(backend.state().await?, BackendState::Starting { eta: None });
+        assert!(backend.resolve().await?.is_none());
+
+        // the server comes up
+        let _listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        assert_eq!(backend.state().await?, BackendState::Running);
+        let transfer = backend.resolve().await?.unwrap();
+        assert_eq!(
+            (transfer.hostname.as_str(), transfer.port),
+            ("127.0.0.1", port)
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,208 @@
-use serde::{Deserialize, Serialize};
+use crate::backend::{DOCKER_SOCKET, MacAddress, ReadyCheck, WOL_BROADCAST_ADDRESS, WOL_PORT};
+use crate::connection::JoinMode;
+use crate::session::MOJANG_SESSION_SERVER;
+use serde::Deserialize;
+use std::io;
+use std::path::PathBuf;
 
 #[derive(Deserialize, Debug)]
 pub struct ReceptionistConfig {
-    pub target_instance_name: String,
+    // Where the server runs, and how to start it.
+    backend: Option<BackendConfig>,
+    // The EC2 instance the server runs on, from before other backends were supported. Short for
+    // backend = { type = "ec2", instance_name = "..." }.
+    target_instance_name: Option<String>,
     pub mc_target_port: u16,
     pub mc_target_motd: String,
-}
\ No newline at end of file
+    pub compression_threshold: Option<i32>,
+    #[serde(default)]
+    pub stall: StallConfig,
+    #[serde(default = "default_session_server_url")]
+    pub session_server_url: String,
+    #[serde(default)]
+    pub favicon: FaviconConfig,
+    // How long, in seconds, to reuse the running server's status before pinging it again.
+    #[serde(default = "default_status_cache_ttl")]
+    pub status_cache_ttl: u64,
+    // Whether players are sent to the server with a Transfer or relayed through the receptionist.
+    #[serde(default)]
+    pub join_mode: JoinMode,
+    #[serde(default)]
+    pub forwarding: ForwardingConfig,
+    // Whether every connection starts with a PROXY protocol header from a load balancer, which
+    // carries the client's real address. Connections without one are turned away.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+}
+
+impl ReceptionistConfig {
+    /// Where the server runs, from either `backend` or the older `target_instance_name`.
+    pub fn backend(&self) -> io::Result<BackendConfig> {
+        match (&self.backend, &self.target_instance_name) {
+            (Some(backend), None) => Ok(backend.clone()),
+            (None, Some(instance_name)) => Ok(BackendConfig::Ec2 {
+                instance_name: instance_name.clone(),
+            }),
+            (Some(_), Some(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Set either backend or target_instance_name in the config, not both",
+            )),
+            (None, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "No backend set in the config",
+            )),
+        }
+    }
+}
+
+fn default_session_server_url() -> String {
+    MOJANG_SESSION_SERVER.to_string()
+}
+
+fn default_status_cache_ttl() -> u64 {
+    5
+}
+
+/// Upper bounds, in seconds, on how long each phase of login waits for the server to become
+/// ready. Phases end early as soon as it is.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StallConfig {
+    pub encryption_response: u64,
+    // Clients are held here with keep-alives, so this can be as long as the server takes to boot.
+    pub configuration: u64,
+}
+
+impl Default for StallConfig {
+    fn default() -> Self {
+        StallConfig {
+            encryption_response: 15,
+            configuration: 5 * 60,
+        }
+    }
+}
+
+/// The kind of machine or process the server runs on, selected with `type`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BackendConfig {
+    // An EC2 instance, found by its Name tag.
+    Ec2 { instance_name: String },
+    // A server on this machine, run as a child process, e.g.
+    // command = ["java", "-Xmx4G", "-jar", "server.jar", "nogui"].
+    Process {
+        command: Vec<String>,
+        #[serde(default = "default_working_directory")]
+        working_directory: PathBuf,
+        // Where players are sent. Relayed players are connected from here, so localhost works in
+        // proxy mode, but transferred players need this machine's public address.
+        #[serde(default = "default_local_hostname")]
+        hostname: String,
+    },
+    // Shell commands that start, stop and check on the server, for anything else. The status
+    // command either prints JSON or reports through its exit code, 0 meaning running. The
+    // hostname is only needed if the status command doesn't print one.
+    Hook {
+        start_command: String,
+        status_command: String,
+        stop_command: String,
+        hostname: Option<String>,
+    },
+    // A Docker container on this machine, started and stopped through the Docker Engine API.
+    // Players are sent to the host port that mc_target_port is published on.
+    Docker {
+        container: String,
+        #[serde(default = "default_docker_socket")]
+        socket: PathBuf,
+        #[serde(default = "default_local_hostname")]
+        hostname: String,
+    },
+    // A machine that sleeps, woken with a Wake-on-LAN magic packet when a player joins. The
+    // server should start when it wakes, and is reached on hostname:mc_target_port.
+    Wol {
+        mac: MacAddress,
+        #[serde(default = "default_wol_broadcast_address")]
+        broadcast_address: String,
+        #[serde(default = "default_wol_port")]
+        wol_port: u16,
+        hostname: String,
+        #[serde(default)]
+        ready_check: ReadyCheck,
+    },
+}
+
+fn default_docker_socket() -> PathBuf {
+    PathBuf::from(DOCKER_SOCKET)
+}
+
+fn default_wol_broadcast_address() -> String {
+    WOL_BROADCAST_ADDRESS.to_string()
+}
+
+fn default_wol_port() -> u16 {
+    WOL_PORT
+}
+
+fn default_working_directory() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_local_hostname() -> String {
+    "localhost".to_string()
+}
+
+/// How the player's details are passed on to the server when the receptionist relays their
+/// session, so the server can run in offline mode and still know who they are.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ForwardingConfig {
+    #[default]
+    None,
+    // Velocity modern forwarding, signed with the secret configured on the server.
+    Velocity { secret: String },
+    // BungeeCord legacy forwarding, for servers that don't support anything newer. It isn't
+    // signed, so the server must only accept connections from the receptionist.
+    Bungeecord,
+}
+
+/// Paths to 64x64 PNG server list icons. The per-state icons are optional and fall back to
+/// `default`.
+#[derive(Deserialize, Debug, Default)]
+pub struct FaviconConfig {
+    pub default: Option<String>,
+    pub sleeping: Option<String>,
+    pub starting: Option<String>,
+    pub online: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_instance_name_shorthand() {
+        let config: ReceptionistConfig = toml::from_str(
+            r#"
+            target_instance_name = "minecraft"
+            mc_target_port = 25565
+            mc_target_motd = "A Minecraft Server"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            config.backend(),
+            Ok(BackendConfig::Ec2 { instance_name }) if instance_name == "minecraft"
+        ));
+
+        let config: ReceptionistConfig = toml::from_str(
+            r#"
+            target_instance_name = "minecraft"
+            mc_target_port = 25565
+            mc_target_motd = "A Minecraft Server"
+            backend = { type = "ec2", instance_name = "minecraft" }
+            "#,
+        )
+        .unwrap();
+        assert!(config.backend().is_err());
+    }
+}
@@ -1,127 +1,475 @@
 mod codec;
+mod forwarding;
 mod protocol;
+mod status_ping;
+mod waiting;
 
+use crate::config::{ForwardingConfig, StallConfig};
+use crate::connection::codec::{VarInt, read_frame};
+use crate::connection::forwarding::{
+    VELOCITY_PLAYER_INFO_CHANNEL, bungeecord_server_address, velocity_player_info,
+};
+use crate::connection::protocol::PacketKind;
 use crate::connection::protocol::{
-    EncryptionRequest, EncryptionResponse, Handshake, HandshakeIntent, LoginAcknowledged,
-    LoginSuccess, Message, Packet, PingRequest, PingResponse, StatusRequest,
-    StatusResponse, ClientboundKeepAlive,
+    ClientInformation, ClientboundKeepAlive, ConfigurationDisconnect, ConnectionState,
+    EncryptionRequest, EncryptionResponse, Handshake, HandshakeIntent, LATEST_VERSION_NAME,
+    LegacyPing, LegacyPingResponse, LoginAcknowledged, LoginDisconnect, LoginPluginRequest,
+    LoginPluginResponse, LoginSuccess, Message, Packet, PingRequest, PingResponse, ProtocolVersion,
+    ServerboundKeepAlive, ServerboundKnownPacks, ServerboundPluginMessage, SetCompression,
+    StatusRequest,
 };
+use crate::favicon::Favicons;
+use crate::session::{SessionServer, server_hash};
 use crate::util::AsyncPeek;
 use aes::Aes128;
+use async_trait::async_trait;
 use cfb8::Cfb8;
 use cfb8::cipher::{AsyncStreamCipher, NewCipher};
 use rand::Rng;
-use rsa::pkcs1::EncodeRsaPublicKey;
 use rsa::pkcs8::EncodePublicKey;
 use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
-use std::collections::VecDeque;
+use serde::Deserialize;
+use std::fmt;
+use std::io::Cursor;
 use std::io::ErrorKind::InvalidData;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
-use std::time::Duration;
-use async_trait::async_trait;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::io;
-use tokio::io::{join, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter, ReadBuf};
-use tokio::time::sleep;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, WeakUnboundedSender};
+use tokio::time::{sleep, timeout};
 use uuid::Uuid;
 
-pub use protocol::{LoginStart, Transfer};
+pub use protocol::{LoginStart, ProfileProperty, StatusResponse, TextComponent, Transfer};
+pub use status_ping::{StatusCache, ping_status};
+use waiting::WaitingPlayer;
+pub use waiting::WaitingPlayers;
+
+// How often to send a keep-alive while holding a client in the configuration state. The client
+// times out after 30 seconds without receiving anything.
+const KEEP_ALIVE_INTERVAL: u64 = 5;
+
+// How long the client has to answer a keep-alive, counted from when it was sent. Answers can
+// take longer than the interval on a slow connection, so this is as lenient as the vanilla
+// server.
+const KEEP_ALIVE_TIMEOUT: u64 = 15;
 
-// Buy as much time as possible to allow for the server to come up without timing out.
-const STALL_AMOUNT: u64 = 15;
+// The channel of the login plugin requests used as keep-alives while holding a relayed login.
+const LOGIN_KEEP_ALIVE_CHANNEL: &str = "mc-server-receptionist:keep_alive";
+
+// How often the default TransferHandler::wait_for_transfer checks whether the server is ready.
+const READY_POLL_INTERVAL: u64 = 2;
 
 type AesCfb8 = Cfb8<Aes128>;
 
+/// The state of the server behind the receptionist, as shown in the server list.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ServerState {
+    Sleeping,
+    Starting { eta: Option<Duration> },
+    Online,
+    Stopping,
+    // Can't be started, so joining won't help.
+    Unavailable,
+}
+
+/// How a player is sent on to the server once it's ready.
+#[derive(Deserialize, PartialEq, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinMode {
+    // Send a Transfer packet so the client connects to the server itself. The server needs
+    // accepts-transfers=true.
+    #[default]
+    Transfer,
+    // Relay the session through the receptionist, so the server's address stays hidden. Unless
+    // the player's details are forwarded, the server authenticates the player itself.
+    Proxy,
+}
+
 #[async_trait]
 pub trait TransferHandler: 'static + Send + Sync {
-    async fn on_join(&self, login_start: &LoginStart) -> Option<Transfer>;
+    /// Decides how a client logging in with the given protocol version will reach the server.
+    async fn join_mode(&self, _protocol_version: i32) -> JoinMode {
+        JoinMode::Transfer
+    }
+
+    /// Called once a player has authenticated, or in proxy mode once they've said who they are.
+    /// Returns a Transfer if the server is already running, or an error with a reason to show the
+    /// player if they can't join at all.
+    async fn on_join(
+        &self,
+        login_start: &LoginStart,
+        client_addr: SocketAddr,
+    ) -> Result<Option<Transfer>, TextComponent>;
     async fn on_transfer_ready(&self) -> Option<Transfer>;
+
+    /// The current state of the server, if it's known.
+    async fn server_state(&self) -> Option<ServerState> {
+        None
+    }
+
+    /// The status of the running server itself, if it can be reached. When available, this is
+    /// shown in the server list instead of the receptionist's own status.
+    async fn backend_status(&self) -> Option<StatusResponse> {
+        None
+    }
+
+    /// The reason shown to a player who gave up waiting for the server to start.
+    async fn not_ready_reason(&self) -> TextComponent {
+        TextComponent::text("Server is starting, try again in a few minutes")
+    }
+
+    /// Resolves once the server is ready to accept players. Callers bound how long they wait
+    /// with a timeout.
+    async fn wait_for_transfer(&self) -> Transfer {
+        loop {
+            if let Some(transfer) = self.on_transfer_ready().await {
+                return transfer;
+            }
+            sleep(Duration::from_secs(READY_POLL_INTERVAL)).await;
+        }
+    }
+}
+
+/// Settings shared by every connection.
+pub struct ConnectionOptions {
+    pub motd: TextComponent,
+    // The threshold to enable during login, if any.
+    pub compression_threshold: Option<i32>,
+    pub stall: StallConfig,
+    pub session_server: SessionServer,
+    pub favicons: Favicons,
+    pub forwarding: ForwardingConfig,
 }
 
-pub struct Connection<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin> {
-    stream: S,
-    send_queue: Arc<Mutex<VecDeque<Packet>>>,
-    path: Option<HandshakeIntent>,
+pub struct Connection<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin + Send + 'static> {
+    stream: Option<S>,
+    client_addr: SocketAddr,
+    // Everything written to the client goes through this channel to the writer task, so packets
+    // can be sent at any time regardless of what the reader is doing.
+    sender: UnboundedSender<Outgoing>,
+    receiver: Option<UnboundedReceiver<Outgoing>>,
+    state: ConnectionState,
+    // The protocol the client asked for in its handshake, and the closest one spoken here.
+    client_protocol: Option<i32>,
+    version: &'static ProtocolVersion,
+    join_mode: JoinMode,
+    // Where to relay the session to in proxy mode, once the server is ready.
+    relay_to: Option<Transfer>,
+    login_hold: Arc<Mutex<LoginHold>>,
     crypto: Crypto,
+    options: Arc<ConnectionOptions>,
+    // The threshold currently in effect for packets read from the client.
+    compression: Option<i32>,
     player_uuid: Option<Uuid>,
     player_username: Option<String>,
-    transfer_handler: Box<dyn TransferHandler>,
+    player_properties: Vec<ProfileProperty>,
+    transfer_handler: Arc<dyn TransferHandler>,
     transfer: Option<Transfer>,
-    motd: String,
+    waiting_players: Arc<WaitingPlayers>,
+    // Lists this player in the server list for as long as they're waiting for the server.
+    waiting: Option<WaitingPlayer>,
+    // The ID of the keep-alive sent to the client that hasn't been answered yet.
+    pending_keep_alive: Arc<Mutex<Option<i64>>>,
 }
 
-impl<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin> Connection<S> {
-    pub fn new<J: TransferHandler>(stream: S, transfer_handler: J, motd: String) -> Self {
+impl<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin + Send + 'static> Connection<S> {
+    pub fn new<J: TransferHandler>(
+        stream: S,
+        client_addr: SocketAddr,
+        transfer_handler: J,
+        options: Arc<ConnectionOptions>,
+        waiting_players: Arc<WaitingPlayers>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
         Connection {
-            stream,
-            send_queue: Arc::new(Mutex::new(VecDeque::new())),
-            path: None,
+            stream: Some(stream),
+            client_addr,
+            sender,
+            receiver: Some(receiver),
+            state: ConnectionState::Handshaking,
+            client_protocol: None,
+            version: ProtocolVersion::latest(),
+            join_mode: JoinMode::Transfer,
+            relay_to: None,
+            login_hold: Arc::new(Mutex::new(LoginHold::default())),
             crypto: Crypto::new(),
+            options,
+            compression: None,
             player_uuid: None,
             player_username: None,
-            transfer_handler: Box::new(transfer_handler),
+            player_properties: Vec::new(),
+            transfer_handler: Arc::new(transfer_handler),
             transfer: None,
-            motd,
+            waiting_players,
+            waiting: None,
+            pending_keep_alive: Arc::new(Mutex::new(None)),
         }
     }
 
-    pub async fn process(&mut self) -> Result<(), io::Error> {
-        loop {
-            let packets_to_send: Vec<Packet> = {
-                let mut queue = self.send_queue.lock().unwrap();
-                let len = queue.len();
-                queue.drain(0..len).collect()
-            };
+    pub async fn process(mut self) -> Result<(), io::Error> {
+        let stream = self.stream.take().expect("connection already processed");
+        let receiver = self.receiver.take().expect("connection already processed");
 
-            for packet in packets_to_send {
-                println!("sending: {:?}", packet);
+        // Legacy pings aren't framed like modern packets, so they have to be recognized before
+        // anything is read.
+        let mut first_bytes = [0u8; 3];
+        let len = stream.peek(&mut first_bytes).await?;
+        if let Some(ping) = LegacyPing::detect(&first_bytes[..len]) {
+            return self.answer_legacy_ping(stream, ping).await;
+        }
 
-                let mut buf = Vec::new();
-                packet.write_to(&mut buf).await?;
+        let (mut read_half, write_half) = io::split(stream);
+        let writer = tokio::spawn(PacketWriter::new(write_half, receiver).run());
 
-                if let Some(cipher) = &mut self.crypto.encrypt_cipher {
-                    cipher.encrypt(buf.as_mut_slice());
+        let result = self.read_packets(&mut read_half).await;
+        let result = match result {
+            Ok(Some(transfer)) => match self.connect_backend(&transfer).await {
+                Ok(backend) => {
+                    // Take the stream back from the writer, which has sent nothing that isn't
+                    // part of the login so far, and hand both ends over to the relay along with
+                    // the encryption state.
+                    let _ = self.sender.send(Outgoing::Release);
+                    let decrypt_cipher = self.crypto.decrypt_cipher.take();
+                    drop(self);
+                    let writer = writer.await.map_err(io::Error::other)??;
+                    let writer = writer.expect("writer released the stream");
+                    return Connection::relay(
+                        read_half,
+                        writer.writer,
+                        backend,
+                        decrypt_cipher,
+                        writer.encrypt_cipher,
+                    )
+                    .await;
                 }
+                Err(e) => {
+                    eprintln!(
+                        "unable to connect to {}:{}: {}",
+                        transfer.hostname, transfer.port, e
+                    );
+                    Err(io::Error::new(
+                        e.kind(),
+                        DisconnectReason("Unable to reach the server, try again later".into()),
+                    ))
+                }
+            },
+            result => result.map(|_| ()),
+        };
+        if let Err(e) = &result {
+            // Anything else is an internal error, which is logged rather than shown to the player.
+            let reason = e
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<DisconnectReason>())
+                .map(|reason| reason.0.clone())
+                .unwrap_or_else(|| {
+                    eprintln!("error for {}: {}", self.client_addr, e);
+                    TextComponent::text("Unable to join, try again later")
+                });
+            self.disconnect(reason);
+        }
 
-                self.stream.write_all(buf.as_slice()).await?;
-            }
+        // Dropping the connection closes the send channel, which lets the writer finish once
+        // everything queued so far has been written.
+        drop(self);
+        let write_result = writer.await.map_err(io::Error::other)?;
+
+        result.and(write_result.map(|_| ()))
+    }
 
-            // For now, always read when there's nothing to write. This works because the server
-            // currently is always responding to something. After the server needs to send something
-            // unprompted to the client, there should be a separate read and write task with a write
-            // packet queue.
-            let mut buf = vec![0, 1];
-            match self.stream.peek(&mut buf).await {
-                Ok(n) if n == 0 => break,
-                Ok(_) => {}
-                Err(e) => return Err(e),
+    // Opens the server's side of a relayed session, replaying what the client has sent so far.
+    async fn connect_backend(&self, transfer: &Transfer) -> io::Result<TcpStream> {
+        let mut backend = TcpStream::connect((transfer.hostname.as_str(), transfer.port)).await?;
+
+        let server_address = match &self.options.forwarding {
+            ForwardingConfig::Bungeecord => bungeecord_server_address(
+                &transfer.hostname,
+                self.client_addr.ip(),
+                self.forwarded_player()?.0,
+                &self.player_properties,
+            )?,
+            _ => transfer.hostname.clone(),
+        };
+        let handshake = Handshake {
+            protocol_version: self.client_protocol.unwrap_or(self.version.protocol),
+            server_address,
+            server_port: transfer.port,
+            intent: HandshakeIntent::Login,
+        };
+        Packet::new(Message::Handshake(handshake))
+            .write_to(&mut backend, None, self.version)
+            .await?;
+
+        // Clients too old to parse are relayed straight after their handshake, so their Login
+        // Start is still unread and goes through with the rest of the session.
+        if let (Some(username), Some(uuid)) = (&self.player_username, self.player_uuid) {
+            let login_start = LoginStart {
+                username: username.clone(),
+                uuid,
+            };
+            Packet::new(Message::LoginStart(login_start))
+                .write_to(&mut backend, None, self.version)
+                .await?;
+        }
+
+        if let ForwardingConfig::Velocity { secret } = &self.options.forwarding {
+            self.forward_velocity(&mut backend, secret).await?;
+        }
+
+        Ok(backend)
+    }
+
+    // A server with modern forwarding asks for the player's details before anything else, and
+    // holds the login until it gets them.
+    async fn forward_velocity(&self, backend: &mut TcpStream, secret: &str) -> io::Result<()> {
+        let body = read_frame(backend, None).await?;
+        let reader = &mut Cursor::new(body);
+        let id = i32::from_var_int(reader).await?;
+        let request = match self.version.clientbound_kind(ConnectionState::Login, id) {
+            Some(PacketKind::LoginPluginRequest) => LoginPluginRequest::read_from(reader).await?,
+            _ => {
+                return Err(io::Error::new(
+                    InvalidData,
+                    format!(
+                        "Expected a Velocity forwarding request, got packet {:X}",
+                        id
+                    ),
+                ));
             }
+        };
+        if request.channel != VELOCITY_PLAYER_INFO_CHANNEL {
+            return Err(io::Error::new(
+                InvalidData,
+                format!(
+                    "Expected a Velocity forwarding request, got {}",
+                    request.channel
+                ),
+            ));
+        }
 
+        let (uuid, username) = self.forwarded_player()?;
+        let data = velocity_player_info(
+            secret,
+            self.client_addr.ip(),
+            uuid,
+            username,
+            &self.player_properties,
+        )
+        .await?;
+        let response = LoginPluginResponse {
+            message_id: request.message_id,
+            data: Some(data),
+        };
+        Packet::new(Message::LoginPluginResponse(response))
+            .write_to(backend, None, self.version)
+            .await
+    }
+
+    // The player whose details are forwarded, who must have logged in here first.
+    fn forwarded_player(&self) -> io::Result<(Uuid, &str)> {
+        match (self.player_uuid, &self.player_username) {
+            (Some(uuid), Some(username)) => Ok((uuid, username)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Can't forward a player who hasn't logged in",
+            )),
+        }
+    }
+
+    async fn relay(
+        client_reader: ReadHalf<S>,
+        client_writer: WriteHalf<S>,
+        backend: TcpStream,
+        decrypt_cipher: Option<AesCfb8>,
+        encrypt_cipher: Option<AesCfb8>,
+    ) -> io::Result<()> {
+        let (backend_reader, backend_writer) = backend.into_split();
+        let (sent, received) = tokio::try_join!(
+            relay_bytes(
+                client_reader,
+                backend_writer,
+                decrypt_cipher,
+                AesCfb8::decrypt
+            ),
+            relay_bytes(
+                backend_reader,
+                client_writer,
+                encrypt_cipher,
+                AesCfb8::encrypt
+            ),
+        )?;
+        println!(
+            "relay closed after {} bytes sent and {} received",
+            sent, received
+        );
+        Ok(())
+    }
+
+    async fn answer_legacy_ping(&self, mut stream: S, ping: LegacyPing) -> io::Result<()> {
+        // Consume the ping. Anything that follows, like the 1.6 host plugin message, has no
+        // bearing on the response.
+        let mut buf = [0u8; 1024];
+        let _ = stream.read(&mut buf).await?;
+
+        let response = LegacyPingResponse {
+            ping,
+            response: self.status_response().await,
+        };
+        println!("sending legacy ping response: {:?}", response);
+        response.write_to(&mut stream).await?;
+        stream.shutdown().await
+    }
+
+    // Reads packets until the client leaves, or returns where to relay the session to.
+    async fn read_packets<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> io::Result<Option<Transfer>> {
+        loop {
             let packet = if let Some(cipher) = &mut self.crypto.decrypt_cipher {
-                let mut reader = DecryptingReader::new(&mut self.stream, cipher);
-                Packet::read_from(&mut reader, self.path).await
+                let mut reader = DecryptingReader::new(reader, cipher);
+                Packet::read_from(&mut reader, self.state, self.compression, self.version).await
             } else {
-                Packet::read_from(&mut self.stream, self.path).await
+                Packet::read_from(reader, self.state, self.compression, self.version).await
             };
 
-            if let Err(e) = &packet
-                && e.kind() == io::ErrorKind::Unsupported
-            {
-                eprintln!("{}", e);
-                continue;
+            match &packet {
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+                // the client closed the connection
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                _ => {}
             }
 
             let packet = packet?;
             println!("received: {:?}", packet);
 
             match packet.message {
-                Message::Handshake(handshake) => self.recv_handshake(handshake)?,
-                Message::StatusRequest(request) => self.recv_status_request(request)?,
+                Message::Handshake(handshake) => self.recv_handshake(handshake).await?,
+                Message::StatusRequest(request) => self.recv_status_request(request).await?,
                 Message::PingRequest(request) => self.recv_ping_request(request)?,
                 Message::LoginStart(login_start) => self.recv_login_start(login_start).await?,
-                Message::EncryptionResponse(response) => self.recv_encryption_response(response).await?,
+                Message::EncryptionResponse(response) => {
+                    self.recv_encryption_response(response).await?
+                }
                 Message::LoginAcknowledged(ack) => self.recv_login_ack(ack).await?,
+                Message::ClientInformation(information) => {
+                    self.recv_client_information(information)?
+                }
+                Message::ServerboundPluginMessage(message) => self.recv_plugin_message(message)?,
+                Message::ServerboundKnownPacks(packs) => self.recv_known_packs(packs)?,
+                Message::ServerboundKeepAlive(keep_alive) => self.recv_keep_alive(keep_alive)?,
+                Message::LoginPluginResponse(response) => {
+                    self.recv_login_plugin_response(response)?
+                }
                 _ => {
                     return Err(io::Error::new(
                         io::ErrorKind::Unsupported,
@@ -129,17 +477,57 @@ impl<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin> Connection<S> {
                     ));
                 }
             }
+
+            if let Some(transfer) = self.relay_to.take() {
+                return Ok(Some(transfer));
+            }
         }
 
-        Ok(())
+        Ok(None)
     }
 
-    async fn stall(&self, stall_amount: u64) {
+    fn send(&self, packet: Packet) {
+        // If the writer has stopped, its error is reported when the connection finishes.
+        let _ = self.sender.send(Outgoing::Packet(Box::new(packet)));
+    }
+
+    fn disconnect(&self, reason: TextComponent) {
+        Connection::<S>::send_disconnect(&self.sender, self.state, reason);
+    }
+
+    // Tells the client why it's being disconnected, if the state has a way to, and closes the
+    // connection.
+    fn send_disconnect(
+        sender: &UnboundedSender<Outgoing>,
+        state: ConnectionState,
+        reason: TextComponent,
+    ) {
+        let message = match state {
+            ConnectionState::Login => Some(Message::LoginDisconnect(LoginDisconnect { reason })),
+            ConnectionState::Configuration => {
+                Some(Message::ConfigurationDisconnect(ConfigurationDisconnect {
+                    reason,
+                }))
+            }
+            _ => None,
+        };
+        if let Some(message) = message {
+            let _ = sender.send(Outgoing::Packet(Box::new(Packet::new(message))));
+        }
+        let _ = sender.send(Outgoing::Shutdown);
+    }
+
+    async fn stall(&mut self, stall_limit: u64) {
         // A Transfer wasn't received from the initial join, which means the server is going to be
-        // starting up. Stall as long as possible so that the user doesn't need to click out and
-        // back in again.
-        if self.transfer.is_none() {
-            sleep(Duration::from_secs(stall_amount)).await;
+        // starting up. Stall until it's ready, or for as long as this phase allows, so that the
+        // user doesn't need to click out and back in again.
+        if self.transfer.is_some() {
+            return;
+        }
+
+        let limit = Duration::from_secs(stall_limit);
+        if let Ok(transfer) = timeout(limit, self.transfer_handler.wait_for_transfer()).await {
+            self.transfer = Some(transfer);
         }
     }
 
@@ -152,39 +540,164 @@ impl<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin> Connection<S> {
         }
     }
 
-    fn recv_handshake(&mut self, handshake: Handshake) -> Result<(), io::Error> {
-        self.path = Some(handshake.intent);
+    async fn recv_handshake(&mut self, handshake: Handshake) -> Result<(), io::Error> {
+        self.state = ConnectionState::from(handshake.intent);
+
+        // Clients newer than any known version most likely still use the latest layout.
+        self.client_protocol = Some(handshake.protocol_version);
+        self.version =
+            ProtocolVersion::get(handshake.protocol_version).unwrap_or(ProtocolVersion::latest());
+        let _ = self.sender.send(Outgoing::SetVersion(self.version));
+
+        if self.state == ConnectionState::Login {
+            self.join_mode = self
+                .transfer_handler
+                .join_mode(handshake.protocol_version)
+                .await;
+        }
+
+        if self.state == ConnectionState::Login && self.client_too_old() {
+            let oldest = ProtocolVersion::oldest().name;
+            // Forwarding vouches for who the player is, which can't be done without understanding
+            // their login, so these clients can't be relayed or sent to the server directly.
+            let reason = if !matches!(self.options.forwarding, ForwardingConfig::None) {
+                format!(
+                    "Your version of Minecraft is too old to join. Join with {oldest} or newer."
+                )
+            } else {
+                let transfer = self.transfer_handler.on_transfer_ready().await;
+                match (self.join_mode, transfer) {
+                    // A relay doesn't need to understand the client, as long as the server does.
+                    (JoinMode::Proxy, Some(transfer)) => {
+                        self.relay_to = Some(transfer);
+                        return Ok(());
+                    }
+                    (JoinMode::Proxy, None) => format!(
+                        "Your version of Minecraft is too old to start the server. Join with \
+                        {oldest} or newer, or try again once it's running."
+                    ),
+                    // There's no way to transfer this client, so don't start the server on its
+                    // behalf. Login Disconnect hasn't changed in years, so older clients can
+                    // still read it.
+                    (JoinMode::Transfer, Some(transfer)) => format!(
+                        "Your version of Minecraft can't join through this address. Connect \
+                        directly to {}:{} instead.",
+                        transfer.hostname, transfer.port
+                    ),
+                    (JoinMode::Transfer, None) => format!(
+                        "Your version of Minecraft is too old to start the server. Join with \
+                        {oldest} or newer."
+                    ),
+                }
+            };
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                DisconnectReason(TextComponent::text(reason)),
+            ));
+        }
         Ok(())
     }
 
-    fn recv_status_request(&mut self, status_request: StatusRequest) -> Result<(), io::Error> {
-        let response = StatusResponse {
-            version_name: "1.21.10".to_string(),
-            version_protocol: 773,
-            max_players: 20,
-            online_players: 0,
-            description: self.motd.clone(),
-            favicon: "".to_string(),
-        };
+    // Transfer was added in 1.20.5, so older clients can't be sent on to the server.
+    fn client_too_old(&self) -> bool {
+        matches!(self.client_protocol, Some(protocol) if protocol < ProtocolVersion::oldest().protocol)
+    }
+
+    async fn recv_status_request(&mut self, _status_request: StatusRequest) -> io::Result<()> {
+        let response = self.status_response().await;
         let packet = Packet::new(Message::StatusResponse(response));
-        self.send_queue.lock().unwrap().push_back(packet);
+        self.send(packet);
         Ok(())
     }
 
+    async fn status_response(&self) -> StatusResponse {
+        if let Some(mut response) = self.transfer_handler.backend_status().await {
+            if response.favicon.is_none() {
+                response.favicon = self.options.favicons.for_state(Some(ServerState::Online));
+            }
+            // The server itself may accept older clients, but they can't get to it from here.
+            if self.client_too_old() {
+                response.version_name = self.version_name();
+                response.version_protocol = ProtocolVersion::latest().protocol as u32;
+            }
+            return response;
+        }
+
+        // Echo the client's protocol if it's supported, so it's shown as compatible.
+        let version_protocol = match self.client_protocol.and_then(ProtocolVersion::get) {
+            Some(version) => version.protocol,
+            None => ProtocolVersion::latest().protocol,
+        };
+
+        let server_state = self.transfer_handler.server_state().await;
+        StatusResponse {
+            version_name: self.version_name(),
+            version_protocol: version_protocol as u32,
+            max_players: 20,
+            online_players: self.waiting_players.count() as u32,
+            player_samples: self.waiting_players.samples(),
+            description: self.status_description(server_state),
+            favicon: self.options.favicons.for_state(server_state),
+        }
+    }
+
+    // The range of versions that can join, shown in red to clients outside it.
+    fn version_name(&self) -> String {
+        format!("{}-{}", ProtocolVersion::oldest().name, LATEST_VERSION_NAME)
+    }
+
+    // The MOTD, with a second line saying what joining will do.
+    fn status_description(&self, server_state: Option<ServerState>) -> TextComponent {
+        let too_old = format!("<red>Requires {} or newer", ProtocolVersion::oldest().name);
+        let status = match server_state {
+            _ if self.client_too_old() => &too_old,
+            Some(ServerState::Sleeping) => "<gray>Sleeping — join to start",
+            Some(ServerState::Starting { eta: Some(eta) }) => {
+                let eta = match eta.as_secs() {
+                    secs if secs < 60 => format!("{}s", secs),
+                    secs => format!("{}m", secs.div_ceil(60)),
+                };
+                &format!("<yellow>Starting… (ETA {})", eta)
+            }
+            Some(ServerState::Starting { eta: None }) => "<yellow>Starting…",
+            Some(ServerState::Online) => "<green>Online",
+            Some(ServerState::Stopping) => "<gold>Shutting down — try again in a minute",
+            Some(ServerState::Unavailable) => "<red>Unavailable",
+            None => return self.options.motd.clone(),
+        };
+
+        let mut description = TextComponent::text("");
+        description.extra.push(self.options.motd.clone());
+        description.extra.push(TextComponent::text("\n"));
+        description
+            .extra
+            .push(TextComponent::from_mini_message(status));
+        description
+    }
+
     fn recv_ping_request(&mut self, ping_request: PingRequest) -> Result<(), io::Error> {
         let response = PingResponse {
             timestamp: ping_request.timestamp,
         };
         let packet = Packet::new(Message::PingResponse(response));
-        self.send_queue.lock().unwrap().push_back(packet);
+        self.send(packet);
         Ok(())
     }
 
     async fn recv_login_start(&mut self, login_start: LoginStart) -> Result<(), io::Error> {
-        self.transfer = self.transfer_handler.on_join(&login_start).await;
-
         self.player_uuid = Some(login_start.uuid);
-        self.player_username = Some(login_start.username);
+        self.player_username = Some(login_start.username.clone());
+
+        // When the player's details are forwarded, the server takes the receptionist's word for
+        // who they are, so they have to be authenticated here first.
+        let forwarding = !matches!(self.options.forwarding, ForwardingConfig::None);
+        if self.join_mode == JoinMode::Proxy && !forwarding {
+            let transfer = self
+                .transfer_handler
+                .on_join(&login_start, self.client_addr)
+                .await;
+            return self.relay_when_ready(transfer, login_start);
+        }
 
         let public_key = self.crypto.public_key.to_public_key_der().unwrap();
 
@@ -195,10 +708,89 @@ impl<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin> Connection<S> {
             should_authenticate: true,
         };
         let packet = Packet::new(Message::EncryptionRequest(request));
-        // stall for a bit less on the first request to get past the "Establishing Connection..."
-        // message.
-        self.stall(STALL_AMOUNT - 5).await;
-        self.send_queue.lock().unwrap().push_back(packet);
+        self.send(packet);
+        Ok(())
+    }
+
+    // In proxy mode the receptionist only waits for the server to be ready, keeping the client
+    // in the login state in the meantime.
+    fn relay_when_ready(
+        &mut self,
+        transfer: Result<Option<Transfer>, TextComponent>,
+        login_start: LoginStart,
+    ) -> io::Result<()> {
+        let transfer = transfer.map_err(|reason| {
+            io::Error::new(io::ErrorKind::PermissionDenied, DisconnectReason(reason))
+        })?;
+        if let Some(transfer) = transfer {
+            self.relay_to = Some(transfer);
+            return Ok(());
+        }
+
+        let waiting_player = self
+            .waiting_players
+            .add(login_start.username, login_start.uuid);
+        self.waiting = Some(waiting_player);
+
+        // Clients still in the login state can be kept from timing out with plugin requests,
+        // which they answer even if they don't understand them.
+        tokio::spawn(Connection::<S>::hold_login_until_ready(
+            self.sender.downgrade(),
+            self.transfer_handler.clone(),
+            self.login_hold.clone(),
+            Duration::from_secs(self.options.stall.configuration),
+        ));
+        Ok(())
+    }
+
+    async fn hold_login_until_ready(
+        weak_sender: WeakUnboundedSender<Outgoing>,
+        transfer_handler: Arc<dyn TransferHandler>,
+        login_hold: Arc<Mutex<LoginHold>>,
+        stall_limit: Duration,
+    ) {
+        let mut message_id = 0;
+        let send_request = |sender: &UnboundedSender<Outgoing>| {
+            message_id += 1;
+            login_hold.lock().unwrap().pending_request = Some(message_id);
+            let _ = sender.send(login_keep_alive(message_id));
+        };
+        let pending = || login_hold.lock().unwrap().pending_request.is_some();
+        let transfer = Connection::<S>::hold_until_ready(
+            &weak_sender,
+            transfer_handler.as_ref(),
+            ConnectionState::Login,
+            stall_limit,
+            pending,
+            send_request,
+        )
+        .await;
+        let Some(transfer) = transfer else {
+            return;
+        };
+
+        // The relay starts when the reader sees the answer to the last request, so that the
+        // answer isn't passed on to the server.
+        let mut login_hold = login_hold.lock().unwrap();
+        login_hold.ready = Some(transfer);
+        if login_hold.pending_request.is_none()
+            && let Some(sender) = weak_sender.upgrade()
+        {
+            message_id += 1;
+            login_hold.pending_request = Some(message_id);
+            let _ = sender.send(login_keep_alive(message_id));
+        }
+    }
+
+    fn recv_login_plugin_response(&mut self, response: LoginPluginResponse) -> io::Result<()> {
+        let mut login_hold = self.login_hold.lock().unwrap();
+        if login_hold.pending_request.take() != Some(response.message_id) {
+            return Err(io::Error::new(
+                InvalidData,
+                "Unexpected login plugin response",
+            ));
+        }
+        self.relay_to = login_hold.ready.take();
         Ok(())
     }
 
@@ -214,47 +806,251 @@ impl<S: AsyncRead + AsyncWrite + AsyncPeek + Unpin> Connection<S> {
             .decrypt(Pkcs1v15Encrypt, response.verify_token.as_slice())
             .map_err(|_| io::Error::new(InvalidData, "Unable to decrypt verify token"))?;
 
-        println!("shared secret: {:?}", shared_secret);
-        println!("verify token: {:?}", verify_token);
-
         if verify_token != self.crypto.verify_token {
-            return Err(io::Error::new(InvalidData, "invalid verify token"));
+            return Err(io::Error::new(InvalidData, "Invalid verify token"));
         }
 
-        self.crypto.encrypt_cipher = Some(
-            AesCfb8::new_from_slices(shared_secret.as_slice(), shared_secret.as_slice()).unwrap(),
-        );
+        let encrypt_cipher =
+            AesCfb8::new_from_slices(shared_secret.as_slice(), shared_secret.as_slice()).unwrap();
+        let _ = self
+            .sender
+            .send(Outgoing::EnableEncryption(Box::new(encrypt_cipher)));
         self.crypto.decrypt_cipher = Some(
             AesCfb8::new_from_slices(shared_secret.as_slice(), shared_secret.as_slice()).unwrap(),
         );
 
+        self.authenticate(&shared_secret).await?;
+
+        // Only start the server for players that have proven who they are.
+        let login_start = LoginStart {
+            username: self.player_username.clone().unwrap(),
+            uuid: self.player_uuid.unwrap(),
+        };
+        let transfer = self
+            .transfer_handler
+            .on_join(&login_start, self.client_addr)
+            .await;
+        if self.join_mode == JoinMode::Proxy {
+            return self.relay_when_ready(transfer, login_start);
+        }
+        self.transfer = transfer.map_err(|reason| {
+            io::Error::new(io::ErrorKind::PermissionDenied, DisconnectReason(reason))
+        })?;
+
         let login_success = LoginSuccess {
             uuid: self.player_uuid.unwrap(),
             username: self.player_username.clone().unwrap(),
+            properties: self.player_properties.clone(),
         };
         let packet = Packet::new(Message::LoginSuccess(login_success));
 
-        self.stall(STALL_AMOUNT).await;
-        self.send_queue.lock().unwrap().push_back(packet);
+        // Players waiting for the server to start show up in the server list until they're
+        // transferred or give up.
+        if self.transfer.is_none() {
+            let waiting_player = self
+                .waiting_players
+                .add(login_start.username, login_start.uuid);
+            self.waiting = Some(waiting_player);
+        }
+
+        self.stall(self.options.stall.encryption_response).await;
+        if let Some(threshold) = self.options.compression_threshold {
+            let set_compression = SetCompression { threshold };
+            let packet = Packet::new(Message::SetCompression(set_compression));
+            self.send(packet);
+
+            // The client can't send anything else until it has received Login Success, by which
+            // point it will have switched to the compressed format.
+            self.compression = Some(threshold);
+        }
+        self.send(packet);
 
         Ok(())
     }
 
-    async fn recv_login_ack(&mut self, ack: LoginAcknowledged) -> io::Result<()> {
-        self.stall(STALL_AMOUNT).await;
+    async fn authenticate(&mut self, shared_secret: &[u8]) -> io::Result<()> {
+        let username = self.player_username.clone().unwrap();
+        let public_key = self.crypto.public_key.to_public_key_der().unwrap();
+        let server_hash = server_hash("", shared_secret, public_key.as_bytes());
+
+        let profile = self
+            .options
+            .session_server
+            .has_joined(&username, &server_hash)
+            .await?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    DisconnectReason("Failed to verify username!".into()),
+                )
+            })?;
+
+        if profile.uuid()? != self.player_uuid.unwrap() {
+            eprintln!(
+                "authenticated profile {} does not match login UUID {}",
+                profile.id,
+                self.player_uuid.unwrap()
+            );
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                DisconnectReason("Failed to verify username!".into()),
+            ));
+        }
+
+        // The session server has the canonical capitalization of the username, and the signed
+        // textures property that lets other clients render the player's skin.
+        self.player_username = Some(profile.name);
+        self.player_properties = profile.properties;
+        Ok(())
+    }
+
+    async fn recv_login_ack(&mut self, _ack: LoginAcknowledged) -> io::Result<()> {
+        self.state = ConnectionState::Configuration;
         if let Some(transfer) = self.get_transfer().await {
             let packet = Packet::new(Message::Transfer(transfer));
-            self.send_queue.lock().unwrap().push_back(packet);
+            self.send(packet);
+            return Ok(());
+        }
+
+        // The server is still starting. The client can be held in the configuration state
+        // indefinitely as long as it keeps receiving keep-alives, so keep it here until the
+        // server is ready.
+        tokio::spawn(Connection::<S>::hold_configuration_until_ready(
+            self.sender.downgrade(),
+            self.transfer_handler.clone(),
+            self.pending_keep_alive.clone(),
+            Duration::from_secs(self.options.stall.configuration),
+        ));
+        Ok(())
+    }
+
+    async fn hold_configuration_until_ready(
+        weak_sender: WeakUnboundedSender<Outgoing>,
+        transfer_handler: Arc<dyn TransferHandler>,
+        pending_keep_alive: Arc<Mutex<Option<i64>>>,
+        stall_limit: Duration,
+    ) {
+        let send_keep_alive = |sender: &UnboundedSender<Outgoing>| {
+            // Like the vanilla server, use the current time as the keep-alive ID.
+            let keep_alive_id = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as i64)
+                .unwrap_or_default();
+            *pending_keep_alive.lock().unwrap() = Some(keep_alive_id);
+
+            let keep_alive = ClientboundKeepAlive { keep_alive_id };
+            let packet = Packet::new(Message::ClientboundKeepAlive(keep_alive));
+            let _ = sender.send(Outgoing::Packet(Box::new(packet)));
+        };
+        let pending = || pending_keep_alive.lock().unwrap().is_some();
+        let transfer = Connection::<S>::hold_until_ready(
+            &weak_sender,
+            transfer_handler.as_ref(),
+            ConnectionState::Configuration,
+            stall_limit,
+            pending,
+            send_keep_alive,
+        )
+        .await;
+        if let Some(transfer) = transfer
+            && let Some(sender) = weak_sender.upgrade()
+        {
+            let packet = Packet::new(Message::Transfer(transfer));
+            let _ = sender.send(Outgoing::Packet(Box::new(packet)));
+        }
+    }
+
+    // Keeps a client waiting on the server from timing out by sending it keep-alives, each of
+    // which it has until the timeout to answer. Returns where to send the client once the server
+    // is ready, or None if the client left or has been disconnected.
+    async fn hold_until_ready(
+        weak_sender: &WeakUnboundedSender<Outgoing>,
+        transfer_handler: &dyn TransferHandler,
+        state: ConnectionState,
+        stall_limit: Duration,
+        keep_alive_pending: impl Fn() -> bool,
+        mut send_keep_alive: impl FnMut(&UnboundedSender<Outgoing>),
+    ) -> Option<Transfer> {
+        let boot_wait_start = Instant::now();
+        let mut keep_alive_sent = Instant::now();
+        // One wait for the whole hold, so the server isn't polled afresh on every keep-alive.
+        let mut ready = transfer_handler.wait_for_transfer();
+        loop {
+            // Once the connection is gone, there's nobody left to hold.
+            let sender = weak_sender.upgrade()?;
+
+            // An unanswered keep-alive is given until the timeout rather than followed by another.
+            let pending = keep_alive_pending();
+            if pending && keep_alive_sent.elapsed() > Duration::from_secs(KEEP_ALIVE_TIMEOUT) {
+                let reason = TextComponent::text("Timed out");
+                Connection::<S>::send_disconnect(&sender, state, reason);
+                return None;
+            }
+
+            let waited = boot_wait_start.elapsed();
+            if waited > stall_limit {
+                eprintln!("server not ready after waiting {}s", waited.as_secs());
+                let reason = transfer_handler.not_ready_reason().await;
+                Connection::<S>::send_disconnect(&sender, state, reason);
+                return None;
+            }
+
+            if !pending {
+                send_keep_alive(&sender);
+                keep_alive_sent = Instant::now();
+            }
+
+            drop(sender);
+
+            // Stop holding as soon as the server is ready rather than at the next keep-alive.
+            let interval = Duration::from_secs(KEEP_ALIVE_INTERVAL);
+            if let Ok(transfer) = timeout(interval, &mut ready).await {
+                return Some(transfer);
+            }
+        }
+    }
+
+    fn recv_keep_alive(&mut self, keep_alive: ServerboundKeepAlive) -> io::Result<()> {
+        let mut pending_keep_alive = self.pending_keep_alive.lock().unwrap();
+        if pending_keep_alive.take() != Some(keep_alive.keep_alive_id) {
+            return Err(io::Error::new(InvalidData, "Invalid keep alive"));
         }
         Ok(())
     }
+
+    fn recv_client_information(&mut self, _information: ClientInformation) -> io::Result<()> {
+        // Client settings only matter to the real server, which receives them again after the
+        // transfer.
+        Ok(())
+    }
+
+    fn recv_plugin_message(&mut self, _message: ServerboundPluginMessage) -> io::Result<()> {
+        // e.g. minecraft:brand, which there is no use for here.
+        Ok(())
+    }
+
+    fn recv_known_packs(&mut self, _packs: ServerboundKnownPacks) -> io::Result<()> {
+        // Known packs are only sent in reply to a clientbound Known Packs request, which the
+        // receptionist never makes, so there's nothing to negotiate.
+        Ok(())
+    }
+}
+
+// A login plugin request the client answers even though it doesn't know the channel, used as a
+// keep-alive while it's held in the login state.
+fn login_keep_alive(message_id: i32) -> Outgoing {
+    let request = LoginPluginRequest {
+        message_id,
+        channel: LOGIN_KEEP_ALIVE_CHANNEL.to_string(),
+        data: Vec::new(),
+    };
+    Outgoing::Packet(Box::new(Packet::new(Message::LoginPluginRequest(request))))
 }
 
 struct Crypto {
     private_key: RsaPrivateKey,
     public_key: RsaPublicKey,
     verify_token: Vec<u8>,
-    encrypt_cipher: Option<AesCfb8>,
     decrypt_cipher: Option<AesCfb8>,
 }
 
@@ -272,12 +1068,128 @@ impl Crypto {
             private_key,
             public_key,
             verify_token,
-            encrypt_cipher: None,
             decrypt_cipher: None,
         }
     }
 }
 
+// A login being held in proxy mode while the server starts.
+#[derive(Default)]
+struct LoginHold {
+    // The ID of the plugin request sent to the client that hasn't been answered yet.
+    pending_request: Option<i32>,
+    // Where to relay to once the pending request is answered.
+    ready: Option<Transfer>,
+}
+
+// An error carrying a formatted message for the player.
+#[derive(Debug)]
+struct DisconnectReason(TextComponent);
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.to_plain())
+    }
+}
+
+impl std::error::Error for DisconnectReason {}
+
+enum Outgoing {
+    Packet(Box<Packet>),
+    // Encrypt everything written after this point.
+    EnableEncryption(Box<AesCfb8>),
+    // Write packets in the layout of this protocol version.
+    SetVersion(&'static ProtocolVersion),
+    // Close the connection.
+    Shutdown,
+    // Stop writing and hand the stream back, to be relayed to the server.
+    Release,
+}
+
+struct PacketWriter<W: AsyncWrite + Unpin> {
+    writer: W,
+    receiver: UnboundedReceiver<Outgoing>,
+    encrypt_cipher: Option<AesCfb8>,
+    compression: Option<i32>,
+    version: &'static ProtocolVersion,
+}
+
+impl<W: AsyncWrite + Unpin> PacketWriter<W> {
+    pub fn new(writer: W, receiver: UnboundedReceiver<Outgoing>) -> Self {
+        PacketWriter {
+            writer,
+            receiver,
+            encrypt_cipher: None,
+            compression: None,
+            version: ProtocolVersion::latest(),
+        }
+    }
+
+    // Returns the writer if it was released rather than shut down.
+    pub async fn run(mut self) -> io::Result<Option<Self>> {
+        while let Some(outgoing) = self.receiver.recv().await {
+            match outgoing {
+                Outgoing::Packet(packet) => self.write_packet(*packet).await?,
+                Outgoing::EnableEncryption(cipher) => self.encrypt_cipher = Some(*cipher),
+                Outgoing::SetVersion(version) => self.version = version,
+                Outgoing::Shutdown => break,
+                Outgoing::Release => return Ok(Some(self)),
+            }
+        }
+
+        self.writer.shutdown().await?;
+        Ok(None)
+    }
+
+    async fn write_packet(&mut self, packet: Packet) -> io::Result<()> {
+        println!("sending: {:?}", packet);
+
+        let mut buf = Vec::new();
+        packet
+            .write_to(&mut buf, self.compression, self.version)
+            .await?;
+
+        if let Some(cipher) = &mut self.encrypt_cipher {
+            cipher.encrypt(buf.as_mut_slice());
+        }
+
+        self.writer.write_all(buf.as_slice()).await?;
+
+        // Set Compression itself is sent uncompressed, but every packet after it uses the
+        // compressed format.
+        if let Message::SetCompression(set_compression) = &packet.message {
+            self.compression = Some(set_compression.threshold);
+        }
+
+        Ok(())
+    }
+}
+
+// Copies bytes one way through a relay, decrypting or encrypting them on the way.
+async fn relay_bytes<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    mut reader: R,
+    mut writer: W,
+    mut cipher: Option<AesCfb8>,
+    apply_cipher: fn(&mut AesCfb8, &mut [u8]),
+) -> io::Result<u64> {
+    let mut buf = vec![0u8; 8192];
+    let mut total = 0;
+    loop {
+        let len = reader.read(&mut buf).await?;
+        if len == 0 {
+            break;
+        }
+        if let Some(cipher) = &mut cipher {
+            apply_cipher(cipher, &mut buf[..len]);
+        }
+        writer.write_all(&buf[..len]).await?;
+        total += len as u64;
+    }
+
+    writer.shutdown().await?;
+    Ok(total)
+}
+
 struct DecryptingReader<'a, R: AsyncRead + Unpin> {
     reader: &'a mut R,
     cipher: &'a mut AesCfb8,
@@ -309,3 +1221,166 @@ impl<'a, R: AsyncRead + Unpin> AsyncRead for DecryptingReader<'a, R> {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    struct RelayHandler {
+        transfer: Transfer,
+    }
+
+    #[async_trait]
+    impl TransferHandler for RelayHandler {
+        async fn join_mode(&self, _protocol_version: i32) -> JoinMode {
+            JoinMode::Proxy
+        }
+
+        async fn on_join(
+            &self,
+            _login_start: &LoginStart,
+            _client_addr: SocketAddr,
+        ) -> Result<Option<Transfer>, TextComponent> {
+            Ok(Some(self.transfer.clone()))
+        }
+
+        async fn on_transfer_ready(&self) -> Option<Transfer> {
+            Some(self.transfer.clone())
+        }
+    }
+
+    fn options(forwarding: ForwardingConfig) -> Arc<ConnectionOptions> {
+        Arc::new(ConnectionOptions {
+            motd: TextComponent::text("A Minecraft Server"),
+            compression_threshold: None,
+            stall: StallConfig::default(),
+            session_server: SessionServer::new("http://127.0.0.1:1".to_string()),
+            favicons: Favicons::default(),
+            forwarding,
+        })
+    }
+
+    #[tokio::test]
+    async fn relay() -> Result<(), io::Error> {
+        let version = ProtocolVersion::latest();
+        let backend = TcpListener::bind("127.0.0.1:0").await?;
+        let transfer = Transfer {
+            hostname: "127.0.0.1".to_string(),
+            port: backend.local_addr()?.port(),
+        };
+
+        let receptionist = TcpListener::bind("127.0.0.1:0").await?;
+        let receptionist_addr = receptionist.local_addr()?;
+        let handler = RelayHandler { transfer };
+        tokio::spawn(async move {
+            let (stream, client_addr) = receptionist.accept().await.unwrap();
+            let waiting_players = Arc::new(WaitingPlayers::default());
+            let options = options(ForwardingConfig::None);
+            let connection =
+                Connection::new(stream, client_addr, handler, options, waiting_players);
+            connection.process().await.unwrap();
+        });
+
+        let mut client = TcpStream::connect(receptionist_addr).await?;
+        let handshake = Handshake {
+            protocol_version: version.protocol,
+            server_address: "mc.example.com".to_string(),
+            server_port: 25565,
+            intent: HandshakeIntent::Login,
+        };
+        Packet::new(Message::Handshake(handshake))
+            .write_to(&mut client, None, version)
+            .await?;
+        let login_start = LoginStart {
+            username: "Notch".to_string(),
+            uuid: Uuid::from_u128(1),
+        };
+        Packet::new(Message::LoginStart(login_start))
+            .write_to(&mut client, None, version)
+            .await?;
+
+        // The server sees the handshake and login replayed, followed by whatever the client
+        // sends next.
+        let (mut server, _) = backend.accept().await?;
+        let packet =
+            Packet::read_from(&mut server, ConnectionState::Handshaking, None, version).await?;
+        let Message::Handshake(handshake) = packet.message else {
+            panic!("expected a handshake, got {:?}", packet.message);
+        };
+        assert_eq!(handshake.protocol_version, version.protocol);
+        assert_eq!(handshake.intent, HandshakeIntent::Login);
+        let packet = Packet::read_from(&mut server, ConnectionState::Login, None, version).await?;
+        let Message::LoginStart(login_start) = packet.message else {
+            panic!("expected login start, got {:?}", packet.message);
+        };
+        assert_eq!(login_start.username, "Notch");
+        assert_eq!(login_start.uuid, Uuid::from_u128(1));
+
+        client.write_all(b"from client").await?;
+        let mut buf = [0u8; 11];
+        server.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"from client");
+
+        server.write_all(b"from server").await?;
+        client.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"from server");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn too_old_with_forwarding() -> Result<(), io::Error> {
+        // Nothing may connect to the server on this client's behalf.
+        let backend = TcpListener::bind("127.0.0.1:0").await?;
+        let transfer = Transfer {
+            hostname: "127.0.0.1".to_string(),
+            port: backend.local_addr()?.port(),
+        };
+
+        let receptionist = TcpListener::bind("127.0.0.1:0").await?;
+        let receptionist_addr = receptionist.local_addr()?;
+        let handler = RelayHandler { transfer };
+        let forwarding = ForwardingConfig::Velocity {
+            secret: "secret".to_string(),
+        };
+        tokio::spawn(async move {
+            let (stream, client_addr) = receptionist.accept().await.unwrap();
+            let waiting_players = Arc::new(WaitingPlayers::default());
+            let options = options(forwarding);
+            let connection =
+                Connection::new(stream, client_addr, handler, options, waiting_players);
+            let _ = connection.process().await;
+        });
+
+        // 1.20.4
+        let mut client = TcpStream::connect(receptionist_addr).await?;
+        let handshake = Handshake {
+            protocol_version: 765,
+            server_address: "mc.example.com".to_string(),
+            server_port: 25565,
+            intent: HandshakeIntent::Login,
+        };
+        let version = ProtocolVersion::oldest();
+        Packet::new(Message::Handshake(handshake))
+            .write_to(&mut client, None, version)
+            .await?;
+
+        let body = read_frame(&mut client, None).await?;
+        let reader = &mut Cursor::new(body);
+        assert_eq!(
+            Some(i32::from_var_int(reader).await?),
+            version.packet_id(PacketKind::LoginDisconnect)
+        );
+        let mut reason = String::new();
+        reader.read_to_string(&mut reason).await?;
+        assert!(reason.contains("too old to join"), "{}", reason);
+
+        assert!(
+            timeout(Duration::from_millis(200), backend.accept())
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
-use byteorder::ReadBytesExt;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
 use std::io;
+use std::io::{Cursor, Read, Write};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 pub trait VarInt {
@@ -26,7 +29,7 @@ impl VarInt for i32 {
             }
 
             position += 7;
-            if (position >= 7 * Self::MAX_BYTES) {
+            if position >= 7 * Self::MAX_BYTES {
                 return Err(io::Error::new(io::ErrorKind::InvalidData, "VarInt too big"));
             }
         }
@@ -128,10 +131,109 @@ impl PrefixedArrayItem for u8 {
     }
 
     async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
-        writer.write_u8(self.clone()).await
+        writer.write_u8(*self).await
     }
 }
 
+// The largest packet length a VarInt-prefixed frame may declare (3 VarInt bytes).
+const MAX_PACKET_LENGTH: i32 = 2097151;
+
+/// Reads one length-prefixed packet frame and returns its body (packet id and data).
+///
+/// If a compression threshold is set, the frame is expected to use the compressed format: a
+/// data length VarInt followed by either the raw body (data length 0) or the zlib-compressed body.
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    compression_threshold: Option<i32>,
+) -> io::Result<Vec<u8>> {
+    let length = i32::from_var_int(reader).await?;
+    if !(0..=MAX_PACKET_LENGTH).contains(&length) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid packet length: {}", length),
+        ));
+    }
+
+    let mut frame = vec![0; length as usize];
+    reader.read_exact(&mut frame).await?;
+
+    let Some(threshold) = compression_threshold else {
+        return Ok(frame);
+    };
+
+    let mut cursor = Cursor::new(frame);
+    let data_length = i32::from_var_int(&mut cursor).await?;
+    let offset = cursor.position() as usize;
+    let frame = cursor.into_inner();
+
+    if data_length == 0 {
+        return Ok(frame[offset..].to_vec());
+    }
+    if !(0..=MAX_PACKET_LENGTH).contains(&data_length) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid uncompressed data length: {}", data_length),
+        ));
+    }
+    // like vanilla, refuse bodies that should have been sent uncompressed
+    if data_length < threshold {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Compressed data length {} is below the threshold of {}",
+                data_length, threshold
+            ),
+        ));
+    }
+
+    // Inflate at most one byte more than claimed, so a small frame can't expand without bound.
+    let mut body = Vec::with_capacity(data_length as usize);
+    ZlibDecoder::new(&frame[offset..])
+        .take(data_length as u64 + 1)
+        .read_to_end(&mut body)?;
+    if body.len() != data_length as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Decompressed length {} does not match data length {}",
+                body.len(),
+                data_length
+            ),
+        ));
+    }
+
+    Ok(body)
+}
+
+/// Writes a packet body (packet id and data) as a length-prefixed frame.
+///
+/// If a compression threshold is set, bodies at least as large as the threshold are
+/// zlib-compressed, and smaller bodies are sent with a data length of 0.
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    body: &[u8],
+    compression_threshold: Option<i32>,
+) -> io::Result<()> {
+    let mut frame = Vec::new();
+    match compression_threshold {
+        Some(threshold) if threshold >= 0 && body.len() >= threshold as usize => {
+            (body.len() as i32).to_var_int(&mut frame).await?;
+            let mut encoder = ZlibEncoder::new(frame, Compression::default());
+            encoder.write_all(body)?;
+            frame = encoder.finish()?;
+        }
+        Some(_) => {
+            0.to_var_int(&mut frame).await?;
+            frame.extend_from_slice(body);
+        }
+        None => frame.extend_from_slice(body),
+    }
+
+    (frame.len() as i32).to_var_int(writer).await?;
+    writer.write_all(frame.as_slice()).await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -211,4 +313,115 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn uncompressed_frame() -> Result<(), io::Error> {
+        let body = vec![0x00, 0x01, 0x02, 0x03];
+
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &body, None).await?;
+        assert_eq!(buf, vec![0x04, 0x00, 0x01, 0x02, 0x03]);
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(read_frame(&mut cursor, None).await?, body);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compressed_frame_below_threshold() -> Result<(), io::Error> {
+        let body = vec![0x00, 0x01, 0x02, 0x03];
+
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &body, Some(256)).await?;
+        // packet length, a data length of 0, then the raw body
+        assert_eq!(buf, vec![0x05, 0x00, 0x00, 0x01, 0x02, 0x03]);
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(read_frame(&mut cursor, Some(256)).await?, body);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compressed_frame_above_threshold() -> Result<(), io::Error> {
+        let body: Vec<u8> = (0..1024).map(|i| (i % 16) as u8).collect();
+
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &body, Some(256)).await?;
+        assert!(buf.len() < body.len());
+
+        let mut cursor = Cursor::new(buf.clone());
+        let length = i32::from_var_int(&mut cursor).await?;
+        assert_eq!(length as usize, buf.len() - cursor.position() as usize);
+        let data_length = i32::from_var_int(&mut cursor).await?;
+        assert_eq!(data_length as usize, body.len());
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(read_frame(&mut cursor, Some(256)).await?, body);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compressed_frame_bad_data_length() -> Result<(), io::Error> {
+        let body: Vec<u8> = vec![0x2A; 512];
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&body)?;
+        let compressed = encoder.finish()?;
+
+        // claim one more byte than was actually compressed
+        let mut frame = Vec::new();
+        513.to_var_int(&mut frame).await?;
+        frame.extend_from_slice(&compressed);
+
+        let mut buf = Vec::new();
+        (frame.len() as i32).to_var_int(&mut buf).await?;
+        buf.extend_from_slice(&frame);
+
+        let mut cursor = Cursor::new(buf);
+        let result = read_frame(&mut cursor, Some(256)).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compressed_frame_inflation_is_bounded() -> Result<(), io::Error> {
+        // 16 MiB of zeros compresses to a few KiB
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0; 16 * 1024 * 1024])?;
+        let compressed = encoder.finish()?;
+
+        let mut frame = Vec::new();
+        512.to_var_int(&mut frame).await?;
+        frame.extend_from_slice(&compressed);
+        let mut buf = Vec::new();
+        (frame.len() as i32).to_var_int(&mut buf).await?;
+        buf.extend_from_slice(&frame);
+
+        let result = read_frame(&mut Cursor::new(buf), Some(256)).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compressed_frame_below_threshold_data_length() -> Result<(), io::Error> {
+        let body = vec![0x2A; 64];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&body)?;
+        let compressed = encoder.finish()?;
+
+        let mut frame = Vec::new();
+        (body.len() as i32).to_var_int(&mut frame).await?;
+        frame.extend_from_slice(&compressed);
+        let mut buf = Vec::new();
+        (frame.len() as i32).to_var_int(&mut buf).await?;
+        buf.extend_from_slice(&frame);
+
+        let result = read_frame(&mut Cursor::new(buf), Some(256)).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
 }
@@ -0,0 +1,116 @@
+use crate::connection::codec::{PrefixedArrayItem, VarInt, VarIntString};
+use crate::connection::protocol::ProfileProperty;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::IpAddr;
+use tokio::io;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+/// The login plugin channel a server with Velocity modern forwarding asks for the player on.
+pub const VELOCITY_PLAYER_INFO_CHANNEL: &str = "velocity:player_info";
+
+// Forwarding version 1 carries no chat signing key, which every server supporting modern
+// forwarding accepts.
+const VELOCITY_FORWARDING_VERSION: i32 = 1;
+
+/// Builds the answer to a `velocity:player_info` request: the player's details, preceded by an
+/// HMAC-SHA256 signature made with the secret shared with the server.
+pub async fn velocity_player_info(
+    secret: &str,
+    address: IpAddr,
+    uuid: Uuid,
+    username: &str,
+    properties: &[ProfileProperty],
+) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    VELOCITY_FORWARDING_VERSION.to_var_int(&mut payload).await?;
+    address.to_string().to_var_int_string(&mut payload).await?;
+    payload.write_u128(uuid.as_u128()).await?;
+    username.to_string().to_var_int_string(&mut payload).await?;
+    (properties.len() as i32).to_var_int(&mut payload).await?;
+    for property in properties {
+        property.write_to(&mut payload).await?;
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(io::Error::other)?;
+    mac.update(&payload);
+    let mut data = mac.finalize().into_bytes().to_vec();
+    data.extend_from_slice(&payload);
+    Ok(data)
+}
+
+/// Builds the handshake server address for BungeeCord forwarding, which smuggles the player's
+/// details to the server as extra null-separated fields.
+pub fn bungeecord_server_address(
+    hostname: &str,
+    address: IpAddr,
+    uuid: Uuid,
+    properties: &[ProfileProperty],
+) -> io::Result<String> {
+    let properties = serde_json::to_string(properties).map_err(io::Error::other)?;
+    Ok(format!(
+        "{}\0{}\0{}\0{}",
+        hostname,
+        address,
+        uuid.simple(),
+        properties
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::codec::PrefixedArray;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn player_info() -> Result<(), io::Error> {
+        let properties = vec![ProfileProperty {
+            name: "textures".to_string(),
+            value: "dGV4dHVyZXM=".to_string(),
+            signature: None,
+        }];
+        let uuid = Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+        let address = "203.0.113.7".parse().unwrap();
+        let data = velocity_player_info("secret", address, uuid, "Notch", &properties).await?;
+
+        // the server checks the signature over everything after it
+        let (signature, payload) = data.split_at(32);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(payload);
+        assert!(mac.verify_slice(signature).is_ok());
+
+        let mut cursor = Cursor::new(payload);
+        assert_eq!(i32::from_var_int(&mut cursor).await?, 1);
+        assert_eq!(String::from_var_int_string(&mut cursor).await?, "203.0.113.7");
+        assert_eq!(cursor.read_u128().await?, uuid.as_u128());
+        assert_eq!(String::from_var_int_string(&mut cursor).await?, "Notch");
+        let properties = Vec::<ProfileProperty>::from_prefixed_array(&mut cursor).await?;
+        assert_eq!(properties[0].name, "textures");
+
+        Ok(())
+    }
+
+    #[test]
+    fn bungeecord_address() -> Result<(), io::Error> {
+        let properties = vec![ProfileProperty {
+            name: "textures".to_string(),
+            value: "dGV4dHVyZXM=".to_string(),
+            signature: Some("c2lnbmF0dXJl".to_string()),
+        }];
+        let uuid = Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+        let address = "203.0.113.7".parse().unwrap();
+        assert_eq!(
+            bungeecord_server_address("10.0.0.5", address, uuid, &properties)?,
+            "10.0.0.5\x00203.0.113.7\x00069a79f444e94726a5befca90e38aaf5\x00\
+            [{\"name\":\"textures\",\"value\":\"dGV4dHVyZXM=\",\"signature\":\"c2lnbmF0dXJl\"}]"
+        );
+        assert_eq!(
+            bungeecord_server_address("10.0.0.5", address, uuid, &[])?,
+            "10.0.0.5\x00203.0.113.7\x00069a79f444e94726a5befca90e38aaf5\x00[]"
+        );
+        Ok(())
+    }
+}
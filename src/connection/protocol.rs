@@ -1,16 +1,23 @@
-use crate::connection::codec::{VarInt, VarIntString};
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
-
 mod configuration;
 mod handshake;
+mod legacy;
 mod login;
 mod packet;
 mod status;
+mod text;
+mod version;
 
-pub use configuration::{Transfer, ClientboundKeepAlive};
+pub use configuration::{
+    AcknowledgeFinishConfiguration, ClientInformation, ClientboundKeepAlive,
+    ConfigurationDisconnect, ServerboundKeepAlive, ServerboundKnownPacks, ServerboundPluginMessage, Transfer,
+};
 pub use handshake::{Handshake, HandshakeIntent, Message};
+pub use legacy::{LegacyPing, LegacyPingResponse};
 pub use login::{
-    EncryptionRequest, EncryptionResponse, LoginAcknowledged, LoginStart, LoginSuccess,
+    EncryptionRequest, EncryptionResponse, LoginAcknowledged, LoginDisconnect, LoginPluginRequest,
+    LoginPluginResponse, LoginStart, LoginSuccess, ProfileProperty, SetCompression,
 };
-pub use packet::Packet;
-pub use status::{PingRequest, PingResponse, StatusRequest, StatusResponse};
+pub use packet::{ConnectionState, Packet};
+pub use status::{PingRequest, PingResponse, PlayerSample, StatusRequest, StatusResponse};
+pub use text::TextComponent;
+pub use version::{PacketKind, ProtocolVersion, LATEST_VERSION_NAME};
@@ -1,6 +1,11 @@
-use crate::connection::codec::{VarInt, VarIntString};
+// Serverbound packets are read in full to keep the stream in step, though it's the real server
+// that makes use of most of what's in them.
+#![allow(dead_code)]
+
+use crate::connection::codec::{PrefixedArray, PrefixedArrayItem, VarInt, VarIntString};
+use crate::connection::protocol::{ProtocolVersion, TextComponent};
 use std::io;
-use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 #[derive(Debug, Clone)]
 pub struct Transfer {
@@ -16,6 +21,22 @@ impl Transfer {
     }
 }
 
+#[derive(Debug)]
+pub struct ConfigurationDisconnect {
+    pub reason: TextComponent,
+}
+
+impl ConfigurationDisconnect {
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        version: &ProtocolVersion,
+    ) -> io::Result<()> {
+        // text components are sent as network NBT in the configuration state
+        writer.write_all(&self.reason.to_nbt(version)).await
+    }
+}
+
 #[derive(Debug)]
 pub struct ClientboundKeepAlive {
     pub keep_alive_id: i64,
@@ -26,3 +47,123 @@ impl ClientboundKeepAlive {
         writer.write_i64(self.keep_alive_id).await
     }
 }
+
+#[derive(Debug)]
+pub struct ClientInformation {
+    pub locale: String,
+    pub view_distance: i8,
+    pub chat_mode: i32,
+    pub chat_colors: bool,
+    pub displayed_skin_parts: u8,
+    pub main_hand: i32,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+    // only sent from 1.21.2 onwards
+    pub particle_status: Option<i32>,
+}
+
+impl ClientInformation {
+    pub async fn read_from<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        version: &ProtocolVersion,
+    ) -> io::Result<Self> {
+        let locale = String::from_var_int_string(reader).await?;
+        let view_distance = reader.read_i8().await?;
+        let chat_mode = i32::from_var_int(reader).await?;
+        let chat_colors = reader.read_u8().await? != 0;
+        let displayed_skin_parts = reader.read_u8().await?;
+        let main_hand = i32::from_var_int(reader).await?;
+        let enable_text_filtering = reader.read_u8().await? != 0;
+        let allow_server_listings = reader.read_u8().await? != 0;
+        let particle_status = match version.client_information_particle_status {
+            true => Some(i32::from_var_int(reader).await?),
+            false => None,
+        };
+
+        Ok(ClientInformation {
+            locale,
+            view_distance,
+            chat_mode,
+            chat_colors,
+            displayed_skin_parts,
+            main_hand,
+            enable_text_filtering,
+            allow_server_listings,
+            particle_status,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ServerboundPluginMessage {
+    pub channel: String,
+    pub data: Vec<u8>,
+}
+
+impl ServerboundPluginMessage {
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let channel = String::from_var_int_string(reader).await?;
+        // the payload is not length prefixed, it runs to the end of the packet
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+
+        Ok(ServerboundPluginMessage { channel, data })
+    }
+}
+
+#[derive(Debug)]
+pub struct AcknowledgeFinishConfiguration {}
+
+#[derive(Debug)]
+pub struct ServerboundKeepAlive {
+    pub keep_alive_id: i64,
+}
+
+impl ServerboundKeepAlive {
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let keep_alive_id = reader.read_i64().await?;
+
+        Ok(ServerboundKeepAlive { keep_alive_id })
+    }
+}
+
+#[derive(Debug)]
+pub struct KnownPack {
+    pub namespace: String,
+    pub id: String,
+    pub version: String,
+}
+
+impl PrefixedArrayItem for KnownPack {
+    async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let namespace = String::from_var_int_string(reader).await?;
+        let id = String::from_var_int_string(reader).await?;
+        let version = String::from_var_int_string(reader).await?;
+
+        Ok(KnownPack {
+            namespace,
+            id,
+            version,
+        })
+    }
+
+    async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.namespace.to_var_int_string(writer).await?;
+        self.id.to_var_int_string(writer).await?;
+        self.version.to_var_int_string(writer).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ServerboundKnownPacks {
+    pub known_packs: Vec<KnownPack>,
+}
+
+impl ServerboundKnownPacks {
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let known_packs = Vec::<KnownPack>::from_prefixed_array(reader).await?;
+
+        Ok(ServerboundKnownPacks { known_packs })
+    }
+}
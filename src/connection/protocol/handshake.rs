@@ -1,11 +1,16 @@
 use crate::connection::codec::{VarInt, VarIntString};
 use crate::connection::protocol::{
-    EncryptionRequest, EncryptionResponse, LoginAcknowledged, LoginStart, LoginSuccess,
-    PingRequest, PingResponse, StatusRequest, StatusResponse, Transfer,
+    EncryptionRequest, EncryptionResponse, LoginAcknowledged, LoginDisconnect, LoginPluginRequest,
+    LoginPluginResponse, LoginStart,
+    LoginSuccess, PingRequest, PingResponse, SetCompression, StatusRequest, StatusResponse,
+    Transfer,
 };
 use std::io;
-use tokio::io::{AsyncRead, AsyncReadExt};
-use crate::connection::protocol::configuration::ClientboundKeepAlive;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::connection::protocol::configuration::{
+    AcknowledgeFinishConfiguration, ClientInformation, ClientboundKeepAlive,
+    ConfigurationDisconnect, ServerboundKeepAlive, ServerboundKnownPacks, ServerboundPluginMessage,
+};
 
 #[derive(Debug)]
 pub enum Message {
@@ -15,12 +20,22 @@ pub enum Message {
     PingRequest(PingRequest),
     PingResponse(PingResponse),
     LoginStart(LoginStart),
+    LoginDisconnect(LoginDisconnect),
     EncryptionRequest(EncryptionRequest),
     EncryptionResponse(EncryptionResponse),
+    SetCompression(SetCompression),
     LoginSuccess(LoginSuccess),
     LoginAcknowledged(LoginAcknowledged),
+    LoginPluginRequest(LoginPluginRequest),
+    LoginPluginResponse(LoginPluginResponse),
+    ClientInformation(ClientInformation),
+    ServerboundPluginMessage(ServerboundPluginMessage),
+    AcknowledgeFinishConfiguration(AcknowledgeFinishConfiguration),
+    ServerboundKnownPacks(ServerboundKnownPacks),
     Transfer(Transfer),
+    ConfigurationDisconnect(ConfigurationDisconnect),
     ClientboundKeepAlive(ClientboundKeepAlive),
+    ServerboundKeepAlive(ServerboundKeepAlive),
 }
 
 #[derive(Debug)]
@@ -45,6 +60,13 @@ impl Handshake {
             intent,
         })
     }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.protocol_version.to_var_int(writer).await?;
+        self.server_address.to_var_int_string(writer).await?;
+        writer.write_u16(self.server_port).await?;
+        self.intent.write_to(writer).await
+    }
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -67,4 +89,13 @@ impl HandshakeIntent {
             )),
         }
     }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        let intent = match self {
+            HandshakeIntent::Status => 1,
+            HandshakeIntent::Login => 2,
+            HandshakeIntent::Transfer => 3,
+        };
+        intent.to_var_int(writer).await
+    }
 }
@@ -0,0 +1,137 @@
+use crate::connection::protocol::StatusResponse;
+use std::io;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+const LEGACY_PING: u8 = 0xFE;
+const LEGACY_KICK: u8 = 0xFF;
+
+// Like the vanilla server, answer with a protocol version no legacy client has, so that they
+// show the version name as incompatible rather than trying to join.
+const LEGACY_PROTOCOL_VERSION: u32 = 127;
+
+/// The server list ping sent by clients from before the Netty rewrite (1.6 and earlier), which
+/// has no length prefix.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LegacyPing {
+    // Beta 1.8 to 1.3: a lone 0xFE.
+    Beta,
+    // 1.4 to 1.6: 0xFE 0x01, which 1.6 follows with a 0xFA plugin message naming the host.
+    V1_4,
+}
+
+impl LegacyPing {
+    /// Recognizes a legacy ping from the first bytes a client sent. This is a heuristic, like
+    /// vanilla's: a modern handshake starting with 0xFE is a legal frame of 254 bytes or more,
+    /// which only an unusually long server address makes.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [LEGACY_PING, 0x01, ..] => Some(LegacyPing::V1_4),
+            [LEGACY_PING, ..] => Some(LegacyPing::Beta),
+            _ => None,
+        }
+    }
+}
+
+/// The kick packet that legacy clients expect in reply to a ping, carrying the status as a
+/// delimited string.
+#[derive(Debug)]
+pub struct LegacyPingResponse {
+    pub ping: LegacyPing,
+    pub response: StatusResponse,
+}
+
+impl LegacyPingResponse {
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        // Legacy clients only show a single line of plain text.
+        let motd = self.response.description.to_plain().replace('\n', " ");
+        let online = self.response.online_players;
+        let max = self.response.max_players;
+
+        let kick = match self.ping {
+            LegacyPing::Beta => format!("{}§{}§{}", motd.replace('§', ""), online, max),
+            LegacyPing::V1_4 => format!(
+                "§1\0{}\0{}\0{}\0{}\0{}",
+                LEGACY_PROTOCOL_VERSION,
+                self.response.version_name,
+                motd.replace('\0', ""),
+                online,
+                max
+            ),
+        };
+
+        // strings are UTF-16 prefixed with their length in code units
+        let kick: Vec<u16> = kick.encode_utf16().collect();
+        writer.write_u8(LEGACY_KICK).await?;
+        writer.write_u16(kick.len() as u16).await?;
+        for unit in kick {
+            writer.write_u16(unit).await?;
+        }
+        writer.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::protocol::TextComponent;
+
+    fn status_response() -> StatusResponse {
+        StatusResponse {
+            version_name: "1.21.10".to_string(),
+            version_protocol: 773,
+            max_players: 20,
+            online_players: 2,
+            player_samples: Vec::new(),
+            description: TextComponent::from_legacy("§aA Minecraft Server\n§7Sleeping"),
+            favicon: None,
+        }
+    }
+
+    fn decode(buf: &[u8]) -> String {
+        assert_eq!(buf[0], LEGACY_KICK);
+        let len = u16::from_be_bytes([buf[1], buf[2]]) as usize;
+        let units: Vec<u16> = buf[3..]
+            .chunks(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        assert_eq!(units.len(), len);
+        String::from_utf16(&units).unwrap()
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(LegacyPing::detect(&[0xFE]), Some(LegacyPing::Beta));
+        assert_eq!(LegacyPing::detect(&[0xFE, 0x01]), Some(LegacyPing::V1_4));
+        assert_eq!(LegacyPing::detect(&[0xFE, 0x01, 0xFA]), Some(LegacyPing::V1_4));
+        // a modern handshake starts with its length
+        assert_eq!(LegacyPing::detect(&[0x10, 0x00, 0xFA]), None);
+        assert_eq!(LegacyPing::detect(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn beta_response() -> Result<(), io::Error> {
+        let response = LegacyPingResponse {
+            ping: LegacyPing::Beta,
+            response: status_response(),
+        };
+        let mut buf = Vec::new();
+        response.write_to(&mut buf).await?;
+        assert_eq!(decode(&buf), "A Minecraft Server Sleeping§2§20");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn v1_4_response() -> Result<(), io::Error> {
+        let response = LegacyPingResponse {
+            ping: LegacyPing::V1_4,
+            response: status_response(),
+        };
+        let mut buf = Vec::new();
+        response.write_to(&mut buf).await?;
+        assert_eq!(
+            decode(&buf),
+            "§1\x00127\x001.21.10\x00A Minecraft Server Sleeping\x002\x0020"
+        );
+        Ok(())
+    }
+}
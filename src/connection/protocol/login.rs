@@ -1,4 +1,6 @@
-use crate::connection::codec::{PrefixedArray, VarInt, VarIntString};
+use crate::connection::codec::{PrefixedArray, PrefixedArrayItem, VarInt, VarIntString};
+use crate::connection::protocol::{ProtocolVersion, TextComponent};
+use serde::{Deserialize, Serialize};
 use std::io;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use uuid::Uuid;
@@ -16,6 +18,28 @@ impl LoginStart {
 
         Ok(LoginStart { username, uuid })
     }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.username.to_var_int_string(writer).await?;
+        writer.write_u128(self.uuid.as_u128()).await
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginDisconnect {
+    pub reason: TextComponent,
+}
+
+impl LoginDisconnect {
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        version: &ProtocolVersion,
+    ) -> io::Result<()> {
+        // the login state still uses JSON text components
+        let reason = self.reason.to_json(version).to_string();
+        reason.to_var_int_string(writer).await
+    }
 }
 
 #[derive(Debug)]
@@ -54,18 +78,72 @@ impl EncryptionResponse {
     }
 }
 
+#[derive(Debug)]
+pub struct SetCompression {
+    pub threshold: i32,
+}
+
+impl SetCompression {
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.threshold.to_var_int(writer).await
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl PrefixedArrayItem for ProfileProperty {
+    async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let name = String::from_var_int_string(reader).await?;
+        let value = String::from_var_int_string(reader).await?;
+        let signature = match reader.read_u8().await? {
+            0 => None,
+            _ => Some(String::from_var_int_string(reader).await?),
+        };
+
+        Ok(ProfileProperty {
+            name,
+            value,
+            signature,
+        })
+    }
+
+    async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.name.to_var_int_string(writer).await?;
+        self.value.to_var_int_string(writer).await?;
+        writer.write_u8(self.signature.is_some() as u8).await?;
+        if let Some(signature) = &self.signature {
+            signature.to_var_int_string(writer).await?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct LoginSuccess {
     pub uuid: Uuid,
     pub username: String,
+    pub properties: Vec<ProfileProperty>,
 }
 
 impl LoginSuccess {
-    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        version: &ProtocolVersion,
+    ) -> io::Result<()> {
         writer.write_u128(self.uuid.as_u128()).await?;
         self.username.to_var_int_string(writer).await?;
-        // empty properties array
-        0.to_var_int(writer).await?;
+        self.properties.to_prefixed_array(writer).await?;
+        if version.login_success_strict_error_handling {
+            // the vanilla server never enables it
+            writer.write_u8(0).await?;
+        }
 
         Ok(())
     }
@@ -73,3 +151,99 @@ impl LoginSuccess {
 
 #[derive(Debug)]
 pub struct LoginAcknowledged {}
+
+#[derive(Debug)]
+pub struct LoginPluginRequest {
+    pub message_id: i32,
+    pub channel: String,
+    pub data: Vec<u8>,
+}
+
+impl LoginPluginRequest {
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let message_id = i32::from_var_int(reader).await?;
+        let channel = String::from_var_int_string(reader).await?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+
+        Ok(LoginPluginRequest {
+            message_id,
+            channel,
+            data,
+        })
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.message_id.to_var_int(writer).await?;
+        self.channel.to_var_int_string(writer).await?;
+        writer.write_all(&self.data).await
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginPluginResponse {
+    pub message_id: i32,
+    // None if the client didn't understand the request.
+    pub data: Option<Vec<u8>>,
+}
+
+impl LoginPluginResponse {
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let message_id = i32::from_var_int(reader).await?;
+        let data = match reader.read_u8().await? {
+            0 => None,
+            _ => {
+                // the payload runs to the end of the packet
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data).await?;
+                Some(data)
+            }
+        };
+
+        Ok(LoginPluginResponse { message_id, data })
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        self.message_id.to_var_int(writer).await?;
+        writer.write_u8(self.data.is_some() as u8).await?;
+        if let Some(data) = &self.data {
+            writer.write_all(data).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn login_success_properties() -> Result<(), io::Error> {
+        let login_success = LoginSuccess {
+            uuid: Uuid::from_u128(1),
+            username: "Notch".to_string(),
+            properties: vec![ProfileProperty {
+                name: "textures".to_string(),
+                value: "dGV4dHVyZXM=".to_string(),
+                signature: Some("c2lnbmF0dXJl".to_string()),
+            }],
+        };
+
+        let mut buf = Vec::new();
+        login_success
+            .write_to(&mut buf, ProtocolVersion::latest())
+            .await?;
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(cursor.read_u128().await?, 1);
+        assert_eq!(String::from_var_int_string(&mut cursor).await?, "Notch");
+        let properties = Vec::<ProfileProperty>::from_prefixed_array(&mut cursor).await?;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].name, "textures");
+        assert_eq!(properties[0].value, "dGV4dHVyZXM=");
+        assert_eq!(properties[0].signature.as_deref(), Some("c2lnbmF0dXJl"));
+
+        Ok(())
+    }
+}
@@ -1,8 +1,36 @@
-use crate::connection::codec::VarInt;
+use crate::connection::codec::{VarInt, read_frame, write_frame};
+use crate::connection::protocol::LoginPluginResponse;
+use crate::connection::protocol::{
+    AcknowledgeFinishConfiguration, ClientInformation, ServerboundKeepAlive, ServerboundKnownPacks,
+    ServerboundPluginMessage,
+};
 use crate::connection::protocol::{EncryptionResponse, Handshake, LoginAcknowledged, LoginStart};
 use crate::connection::protocol::{HandshakeIntent, Message, PingRequest, StatusRequest};
+use crate::connection::protocol::{PacketKind, ProtocolVersion};
 use std::io;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+/// The protocol state of a connection, which determines how packet IDs are interpreted.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ConnectionState {
+    Handshaking,
+    Status,
+    Login,
+    Configuration,
+    // Only the real server gets this far.
+    #[allow(dead_code)]
+    Play,
+}
+
+impl From<HandshakeIntent> for ConnectionState {
+    fn from(intent: HandshakeIntent) -> Self {
+        match intent {
+            HandshakeIntent::Status => ConnectionState::Status,
+            HandshakeIntent::Login | HandshakeIntent::Transfer => ConnectionState::Login,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Packet {
@@ -16,83 +44,106 @@ impl Packet {
 
     pub async fn read_from<R: AsyncRead + Unpin>(
         reader: &mut R,
-        connection_path: Option<HandshakeIntent>,
+        state: ConnectionState,
+        compression_threshold: Option<i32>,
+        version: &ProtocolVersion,
     ) -> io::Result<Self> {
-        let length = i32::from_var_int(reader).await?;
+        // Read the whole frame up front so that unrecognized packets can be skipped without
+        // knowing their layout.
+        let body = read_frame(reader, compression_threshold).await?;
+        let length = body.len();
+        let reader = &mut Cursor::new(body);
         let id = i32::from_var_int(reader).await?;
 
-        let message = match connection_path {
-            None => match id {
-                0x00 => Some(Message::Handshake(Handshake::read_from(reader).await?)),
-                _ => None,
-            },
-            Some(HandshakeIntent::Status) => match id {
-                0x00 => Some(Message::StatusRequest(StatusRequest {})),
-                0x01 => Some(Message::PingRequest(PingRequest::read_from(reader).await?)),
-                _ => None,
-            },
-            Some(HandshakeIntent::Login) => match id {
-                0x00 => Some(Message::LoginStart(LoginStart::read_from(reader).await?)),
-                0x01 => Some(Message::EncryptionResponse(
-                    EncryptionResponse::read_from(reader).await?,
-                )),
-                0x03 => Some(Message::LoginAcknowledged(LoginAcknowledged {})),
-                _ => None,
-            },
-            Some(_) => None,
+        let message = match version.serverbound_kind(state, id) {
+            Some(PacketKind::Handshake) => {
+                Some(Message::Handshake(Handshake::read_from(reader).await?))
+            }
+            Some(PacketKind::StatusRequest) => Some(Message::StatusRequest(StatusRequest {})),
+            Some(PacketKind::PingRequest) => {
+                Some(Message::PingRequest(PingRequest::read_from(reader).await?))
+            }
+            Some(PacketKind::LoginStart) => {
+                Some(Message::LoginStart(LoginStart::read_from(reader).await?))
+            }
+            Some(PacketKind::EncryptionResponse) => Some(Message::EncryptionResponse(
+                EncryptionResponse::read_from(reader).await?,
+            )),
+            Some(PacketKind::LoginPluginResponse) => Some(Message::LoginPluginResponse(
+                LoginPluginResponse::read_from(reader).await?,
+            )),
+            Some(PacketKind::LoginAcknowledged) => {
+                Some(Message::LoginAcknowledged(LoginAcknowledged {}))
+            }
+            Some(PacketKind::ClientInformation) => Some(Message::ClientInformation(
+                ClientInformation::read_from(reader, version).await?,
+            )),
+            Some(PacketKind::ServerboundPluginMessage) => Some(Message::ServerboundPluginMessage(
+                ServerboundPluginMessage::read_from(reader).await?,
+            )),
+            Some(PacketKind::AcknowledgeFinishConfiguration) => Some(
+                Message::AcknowledgeFinishConfiguration(AcknowledgeFinishConfiguration {}),
+            ),
+            Some(PacketKind::ServerboundKeepAlive) => Some(Message::ServerboundKeepAlive(
+                ServerboundKeepAlive::read_from(reader).await?,
+            )),
+            Some(PacketKind::ServerboundKnownPacks) => Some(Message::ServerboundKnownPacks(
+                ServerboundKnownPacks::read_from(reader).await?,
+            )),
+            _ => None,
         };
 
         match message {
             Some(message) => Ok(Packet { message }),
-            None => {
-                let mut unrecognized_bytes = vec![0; length as usize];
-                reader.read_exact(&mut unrecognized_bytes).await?;
-
-                Err(io::Error::new(
-                    io::ErrorKind::Unsupported,
-                    format!(
-                        "Unrecognized packet received: {:X} for path {:?} with len {}",
-                        id, connection_path, length
-                    ),
-                ))
-            }
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "Unrecognized packet received: {:X} for state {:?} with len {}",
+                    id, state, length
+                ),
+            )),
         }
     }
 
-    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        compression_threshold: Option<i32>,
+        version: &ProtocolVersion,
+    ) -> io::Result<()> {
+        let kind = self.message.kind();
+        let packet_id = version.packet_id(kind).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "{:?} has no packet ID in protocol {}",
+                    kind, version.protocol
+                ),
+            )
+        })?;
+
         let mut buf = Vec::new();
+        packet_id.to_var_int(&mut buf).await?;
 
         match &self.message {
-            Message::StatusResponse(response) => {
-                let packet_id = 0x00;
-                packet_id.to_var_int(&mut buf).await?;
-                response.write_to(&mut buf).await?;
-            }
-            Message::PingResponse(response) => {
-                let packet_id = 0x01;
-                packet_id.to_var_int(&mut buf).await?;
-                response.write_to(&mut buf).await?;
-            }
-            Message::EncryptionRequest(request) => {
-                let packet_id = 0x01;
-                packet_id.to_var_int(&mut buf).await?;
-                request.write_to(&mut buf).await?;
-            }
+            Message::Handshake(handshake) => handshake.write_to(&mut buf).await?,
+            Message::LoginStart(login_start) => login_start.write_to(&mut buf).await?,
+            Message::LoginPluginRequest(request) => request.write_to(&mut buf).await?,
+            Message::LoginPluginResponse(response) => response.write_to(&mut buf).await?,
+            Message::StatusRequest(_) => {}
+            Message::StatusResponse(response) => response.write_to(&mut buf, version).await?,
+            Message::PingResponse(response) => response.write_to(&mut buf).await?,
+            Message::LoginDisconnect(disconnect) => disconnect.write_to(&mut buf, version).await?,
+            Message::EncryptionRequest(request) => request.write_to(&mut buf).await?,
+            Message::SetCompression(set_compression) => set_compression.write_to(&mut buf).await?,
             Message::LoginSuccess(login_success) => {
-                let packet_id = 0x02;
-                packet_id.to_var_int(&mut buf).await?;
-                login_success.write_to(&mut buf).await?;
-            }
-            Message::Transfer(transfer) => {
-                let packet_id = 0x0B;
-                packet_id.to_var_int(&mut buf).await?;
-                transfer.write_to(&mut buf).await?;
+                login_success.write_to(&mut buf, version).await?
             }
-            Message::ClientboundKeepAlive(keep_alive) => {
-                let packet_id = 0x04;
-                packet_id.to_var_int(&mut buf).await?;
-                keep_alive.write_to(&mut buf).await?;
+            Message::ConfigurationDisconnect(disconnect) => {
+                disconnect.write_to(&mut buf, version).await?
             }
+            Message::Transfer(transfer) => transfer.write_to(&mut buf).await?,
+            Message::ClientboundKeepAlive(keep_alive) => keep_alive.write_to(&mut buf).await?,
             _ => {
                 return Err(io::Error::new(
                     io::ErrorKind::Unsupported,
@@ -101,11 +152,81 @@ impl Packet {
             }
         }
 
-        (buf.len() as i32).to_var_int(writer).await?;
-        writer.write(buf.as_slice()).await?;
-
+        write_frame(writer, buf.as_slice(), compression_threshold).await?;
         writer.flush().await?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::codec::{VarIntString, write_frame};
+    use crate::connection::protocol::LoginSuccess;
+    use tokio::io::AsyncWriteExt;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn configuration_client_information() -> Result<(), io::Error> {
+        let mut body = Vec::new();
+        0x00.to_var_int(&mut body).await?;
+        "en_us".to_string().to_var_int_string(&mut body).await?;
+        body.write_i8(12).await?;
+        0.to_var_int(&mut body).await?;
+        body.write_u8(1).await?;
+        body.write_u8(0x7F).await?;
+        1.to_var_int(&mut body).await?;
+        body.write_u8(0).await?;
+        body.write_u8(1).await?;
+        0.to_var_int(&mut body).await?;
+
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &body, None).await?;
+
+        // 0x00 is Login Start in the login state, so this must be decoded by state
+        let mut cursor = Cursor::new(buf);
+        let packet = Packet::read_from(
+            &mut cursor,
+            ConnectionState::Configuration,
+            None,
+            ProtocolVersion::latest(),
+        )
+        .await?;
+        match packet.message {
+            Message::ClientInformation(information) => {
+                assert_eq!(information.locale, "en_us");
+                assert_eq!(information.view_distance, 12);
+                assert_eq!(information.displayed_skin_parts, 0x7F);
+                assert_eq!(information.main_hand, 1);
+                assert!(information.allow_server_listings);
+                assert_eq!(information.particle_status, Some(0));
+            }
+            message => panic!("unexpected message: {:?}", message),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn login_success_layout() -> Result<(), io::Error> {
+        let packet = Packet::new(Message::LoginSuccess(LoginSuccess {
+            uuid: Uuid::from_u128(1),
+            username: "Notch".to_string(),
+            properties: Vec::new(),
+        }));
+
+        // 1.20.5 and 1.21 end Login Success with the strict error handling flag
+        let mut old = Vec::new();
+        packet
+            .write_to(&mut old, None, ProtocolVersion::get(767).unwrap())
+            .await?;
+        let mut new = Vec::new();
+        packet
+            .write_to(&mut new, None, ProtocolVersion::latest())
+            .await?;
+        assert_eq!(old.len(), new.len() + 1);
+
+        Ok(())
+    }
+}
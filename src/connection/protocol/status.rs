@@ -1,25 +1,82 @@
-use crate::connection::codec::VarInt;
-use serde_json::json;
+use crate::connection::codec::{VarInt, VarIntString};
+use crate::connection::protocol::{ProtocolVersion, TextComponent};
+use serde_json::{Value, json};
 use std::io;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
 
 #[derive(Debug)]
 pub struct StatusRequest {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerSample {
+    pub name: String,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone)]
 pub struct StatusResponse {
     pub version_name: String,
     pub version_protocol: u32,
     pub max_players: u32,
     pub online_players: u32,
-    //pub player_samples: Vec<PlayerSample<'a>>,
-    pub description: String,
-    pub favicon: String,
+    pub player_samples: Vec<PlayerSample>,
+    pub description: TextComponent,
+    pub favicon: Option<String>,
 }
 
 impl StatusResponse {
-    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
-        let response_json = json!({
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let response_json = String::from_var_int_string(reader).await?;
+        let response_json: Value = serde_json::from_str(&response_json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        StatusResponse::from_json(&response_json)
+    }
+
+    pub fn from_json(response_json: &Value) -> io::Result<Self> {
+        let version = &response_json["version"];
+        let players = &response_json["players"];
+        let version_protocol = version["protocol"].as_u64().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Status response is missing a protocol version",
+            )
+        })?;
+
+        // samples with an invalid UUID are skipped rather than failing the whole response
+        let player_samples = match &players["sample"] {
+            Value::Array(samples) => samples
+                .iter()
+                .filter_map(|sample| {
+                    Some(PlayerSample {
+                        name: sample["name"].as_str()?.to_string(),
+                        id: Uuid::parse_str(sample["id"].as_str()?).ok()?,
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(StatusResponse {
+            version_name: version["name"].as_str().unwrap_or_default().to_string(),
+            version_protocol: version_protocol as u32,
+            max_players: players["max"].as_u64().unwrap_or_default() as u32,
+            online_players: players["online"].as_u64().unwrap_or_default() as u32,
+            player_samples,
+            description: response_json
+                .get("description")
+                .map(TextComponent::from_json)
+                .unwrap_or_default(),
+            favicon: response_json["favicon"].as_str().map(str::to_string),
+        })
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        version: &ProtocolVersion,
+    ) -> io::Result<()> {
+        let mut response_json = json!({
             "version": {
                 "name": self.version_name,
                 "protocol": self.version_protocol,
@@ -28,16 +85,24 @@ impl StatusResponse {
                 "max": self.max_players,
                 "online": self.online_players,
             },
-            "description": {
-                "text": self.description,
-            },
-            "favicon": self.favicon,
+            "description": self.description.to_json(version),
             "enforcesSecureChat": false,
         });
+        if !self.player_samples.is_empty() {
+            let samples: Vec<Value> = self
+                .player_samples
+                .iter()
+                .map(|sample| json!({ "name": sample.name, "id": sample.id.to_string() }))
+                .collect();
+            response_json["players"]["sample"] = Value::Array(samples);
+        }
+        if let Some(favicon) = &self.favicon {
+            response_json["favicon"] = json!(favicon);
+        }
         let response_json = response_json.to_string();
 
         (response_json.len() as i32).to_var_int(writer).await?;
-        writer.write(response_json.as_bytes()).await?;
+        writer.write_all(response_json.as_bytes()).await?;
         writer.flush().await?;
 
         Ok(())
@@ -67,3 +132,54 @@ impl PingResponse {
         writer.write_u64(self.timestamp).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn round_trip() -> Result<(), io::Error> {
+        let response = StatusResponse {
+            version_name: "1.21.10".to_string(),
+            version_protocol: 773,
+            max_players: 20,
+            online_players: 1,
+            player_samples: vec![PlayerSample {
+                name: "Notch".to_string(),
+                id: Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap(),
+            }],
+            description: TextComponent::from_mini_message("<green>A Minecraft Server"),
+            favicon: Some("data:image/png;base64,iVBORw0KGgo=".to_string()),
+        };
+
+        let mut buf = Vec::new();
+        response
+            .write_to(&mut buf, ProtocolVersion::latest())
+            .await?;
+        let read = StatusResponse::read_from(&mut Cursor::new(buf)).await?;
+
+        assert_eq!(read.version_name, response.version_name);
+        assert_eq!(read.version_protocol, response.version_protocol);
+        assert_eq!(read.max_players, response.max_players);
+        assert_eq!(read.online_players, response.online_players);
+        assert_eq!(read.player_samples, response.player_samples);
+        assert_eq!(read.description, response.description);
+        assert_eq!(read.favicon, response.favicon);
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_description() -> Result<(), io::Error> {
+        let response = StatusResponse::from_json(&json!({
+            "version": { "name": "Paper 1.21.10", "protocol": 773 },
+            "players": { "max": 100, "online": 3 },
+            "description": "A Minecraft Server",
+        }))?;
+        assert_eq!(response.description.to_plain(), "A Minecraft Server");
+        assert!(response.player_samples.is_empty());
+        assert_eq!(response.favicon, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,874 @@
+use crate::connection::protocol::ProtocolVersion;
+use byteorder::{BigEndian, WriteBytesExt};
+use serde_json::{Map, Value, json};
+
+const NAMED_COLORS: [&str; 16] = [
+    "black",
+    "dark_blue",
+    "dark_green",
+    "dark_aqua",
+    "dark_red",
+    "dark_purple",
+    "gold",
+    "gray",
+    "dark_gray",
+    "blue",
+    "green",
+    "aqua",
+    "red",
+    "light_purple",
+    "yellow",
+    "white",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextContent {
+    Text(String),
+    Translate {
+        key: String,
+        with: Vec<TextComponent>,
+    },
+}
+
+impl Default for TextContent {
+    fn default() -> Self {
+        TextContent::Text(String::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClickEvent {
+    OpenUrl(String),
+    RunCommand(String),
+    SuggestCommand(String),
+    CopyToClipboard(String),
+    ChangePage(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HoverEvent {
+    ShowText(Box<TextComponent>),
+}
+
+/// A rich text component, as used for MOTDs, disconnect reasons and chat.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextComponent {
+    pub content: TextContent,
+    // either a named color such as "red" or a hex color such as "#FF5555"
+    pub color: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+    pub click_event: Option<ClickEvent>,
+    pub hover_event: Option<HoverEvent>,
+    pub extra: Vec<TextComponent>,
+}
+
+impl TextComponent {
+    pub fn text<T: Into<String>>(text: T) -> Self {
+        TextComponent {
+            content: TextContent::Text(text.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn translate<T: Into<String>>(key: T, with: Vec<TextComponent>) -> Self {
+        TextComponent {
+            content: TextContent::Translate {
+                key: key.into(),
+                with,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Parses configured text, which may use either legacy `§` formatting codes or
+    /// MiniMessage-style tags such as `<red>` and `<bold>`.
+    pub fn parse(input: &str) -> Self {
+        if input.contains('§') {
+            TextComponent::from_legacy(input)
+        } else {
+            TextComponent::from_mini_message(input)
+        }
+    }
+
+    /// Parses text formatted with legacy `§` codes. As in vanilla, a color code resets any
+    /// formatting that came before it.
+    pub fn from_legacy(input: &str) -> Self {
+        let mut root = TextComponent::text("");
+        let mut style = Style::default();
+        let mut text = String::new();
+
+        let mut chars = input.chars();
+        while let Some(c) = chars.next() {
+            if c != '§' {
+                text.push(c);
+                continue;
+            }
+            let Some(code) = chars.next() else {
+                break;
+            };
+
+            let mut next_style = style.clone();
+            match code.to_ascii_lowercase() {
+                code @ ('0'..='9' | 'a'..='f') => {
+                    let index = code.to_digit(16).unwrap() as usize;
+                    next_style = Style {
+                        color: Some(NAMED_COLORS[index].to_string()),
+                        ..Default::default()
+                    };
+                }
+                'k' => next_style.obfuscated = Some(true),
+                'l' => next_style.bold = Some(true),
+                'm' => next_style.strikethrough = Some(true),
+                'n' => next_style.underlined = Some(true),
+                'o' => next_style.italic = Some(true),
+                'r' => next_style = Style::default(),
+                // not a formatting code, so keep it as text
+                _ => {
+                    text.push('§');
+                    text.push(code);
+                    continue;
+                }
+            }
+
+            if !text.is_empty() {
+                root.extra
+                    .push(style.apply(TextComponent::text(text.split_off(0))));
+            }
+            style = next_style;
+        }
+
+        if !text.is_empty() {
+            root.extra.push(style.apply(TextComponent::text(text)));
+        }
+
+        root
+    }
+
+    /// Parses text formatted with a subset of MiniMessage: colors (`<red>`, `<#ff5555>`,
+    /// `<color:red>`), decorations (`<bold>`, `<b>`, `<italic>`, `<i>`, `<underlined>`,
+    /// `<u>`, `<strikethrough>`, `<st>`, `<obfuscated>`, `<obf>`), `<reset>`, `<newline>`,
+    /// `<lang:key>`, `<click:action:'value'>` and `<hover:show_text:'text'>`. Tags are closed
+    /// with `</name>`, and `\<` escapes a literal `<`. Unknown tags are kept as text.
+    pub fn from_mini_message(input: &str) -> Self {
+        let mut root = TextComponent::text("");
+        // each open tag, with the style that applies inside it
+        let mut stack: Vec<(String, Style)> = Vec::new();
+        let mut text = String::new();
+
+        let current = |stack: &Vec<(String, Style)>| -> Style {
+            stack
+                .last()
+                .map(|(_, style)| style.clone())
+                .unwrap_or_default()
+        };
+
+        let mut rest = input;
+        while let Some(c) = rest.chars().next() {
+            if c == '\\' && rest[1..].starts_with('<') {
+                text.push('<');
+                rest = &rest[2..];
+                continue;
+            }
+            if c != '<' {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+
+            let Some(end) = find_tag_end(rest) else {
+                text.push(c);
+                rest = &rest[1..];
+                continue;
+            };
+            let tag = &rest[1..end];
+            let after = &rest[end + 1..];
+
+            let flush = |text: &mut String, root: &mut TextComponent, style: Style| {
+                if !text.is_empty() {
+                    root.extra
+                        .push(style.apply(TextComponent::text(text.split_off(0))));
+                }
+            };
+
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = tag_name(name);
+                if let Some(index) = stack.iter().rposition(|(open, _)| *open == name) {
+                    flush(&mut text, &mut root, current(&stack));
+                    stack.truncate(index);
+                    rest = after;
+                    continue;
+                }
+            } else {
+                let args = split_tag_args(tag);
+                let name = tag_name(&args[0]);
+                match name.as_str() {
+                    "newline" | "br" => {
+                        text.push('\n');
+                        rest = after;
+                        continue;
+                    }
+                    "reset" => {
+                        flush(&mut text, &mut root, current(&stack));
+                        stack.clear();
+                        rest = after;
+                        continue;
+                    }
+                    "lang" | "tr" | "translate" if args.len() > 1 => {
+                        flush(&mut text, &mut root, current(&stack));
+                        let with = args[2..]
+                            .iter()
+                            .map(|arg| TextComponent::from_mini_message(arg))
+                            .collect();
+                        let component = TextComponent::translate(args[1].clone(), with);
+                        root.extra.push(current(&stack).apply(component));
+                        rest = after;
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                let mut style = current(&stack);
+                if style.update(&name, &args) {
+                    flush(&mut text, &mut root, current(&stack));
+                    stack.push((name, style));
+                    rest = after;
+                    continue;
+                }
+            }
+
+            // not a tag we understand, so keep it as text
+            text.push('<');
+            rest = &rest[1..];
+        }
+
+        if !text.is_empty() {
+            root.extra
+                .push(current(&stack).apply(TextComponent::text(text)));
+        }
+
+        root
+    }
+
+    /// Returns the text without any formatting.
+    pub fn to_plain(&self) -> String {
+        let mut plain = match &self.content {
+            TextContent::Text(text) => text.clone(),
+            TextContent::Translate { key, .. } => key.clone(),
+        };
+        for child in &self.extra {
+            plain.push_str(&child.to_plain());
+        }
+        plain
+    }
+
+    /// Parses a JSON text component: a string, an array of components, or an object. Both the
+    /// current snake_case event names and the older camelCase ones are understood.
+    pub fn from_json(json: &Value) -> Self {
+        let object = match json {
+            Value::String(text) => return TextComponent::text(text.clone()),
+            Value::Array(components) => {
+                // the first component is the parent of the rest
+                let mut components = components.iter().map(TextComponent::from_json);
+                let mut root = components.next().unwrap_or_default();
+                root.extra.extend(components);
+                return root;
+            }
+            Value::Object(object) => object,
+            other => return TextComponent::text(other.to_string()),
+        };
+
+        let content = match object.get("translate").and_then(Value::as_str) {
+            Some(key) => {
+                let with = match object.get("with") {
+                    Some(Value::Array(with)) => with.iter().map(TextComponent::from_json).collect(),
+                    _ => Vec::new(),
+                };
+                TextContent::Translate {
+                    key: key.to_string(),
+                    with,
+                }
+            }
+            None => {
+                let text = object
+                    .get("text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                TextContent::Text(text.to_string())
+            }
+        };
+
+        let flag = |name: &str| object.get(name).and_then(Value::as_bool);
+        let event = |name: &str, legacy_name: &str| {
+            object
+                .get(name)
+                .or_else(|| object.get(legacy_name))
+                .and_then(Value::as_object)
+        };
+
+        let click_event = event("click_event", "clickEvent").and_then(|event| {
+            let action = event.get("action")?.as_str()?;
+            let value = |key: &str| {
+                event
+                    .get(key)
+                    .or_else(|| event.get("value"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            };
+            Some(match action {
+                "open_url" => ClickEvent::OpenUrl(value("url")?),
+                "run_command" => ClickEvent::RunCommand(value("command")?),
+                "suggest_command" => ClickEvent::SuggestCommand(value("command")?),
+                "copy_to_clipboard" => ClickEvent::CopyToClipboard(value("value")?),
+                "change_page" => {
+                    let page = event.get("page").or_else(|| event.get("value"))?;
+                    let page = match page {
+                        Value::String(page) => page.parse().ok()?,
+                        page => page.as_i64()? as i32,
+                    };
+                    ClickEvent::ChangePage(page)
+                }
+                _ => return None,
+            })
+        });
+        let hover_event = event("hover_event", "hoverEvent").and_then(|event| {
+            if event.get("action")?.as_str()? != "show_text" {
+                return None;
+            }
+            let text = event.get("value").or_else(|| event.get("contents"))?;
+            Some(HoverEvent::ShowText(Box::new(TextComponent::from_json(
+                text,
+            ))))
+        });
+
+        let extra = match object.get("extra") {
+            Some(Value::Array(extra)) => extra.iter().map(TextComponent::from_json).collect(),
+            _ => Vec::new(),
+        };
+
+        TextComponent {
+            content,
+            color: object
+                .get("color")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            bold: flag("bold"),
+            italic: flag("italic"),
+            underlined: flag("underlined"),
+            strikethrough: flag("strikethrough"),
+            obfuscated: flag("obfuscated"),
+            click_event,
+            hover_event,
+            extra,
+        }
+    }
+
+    /// Encodes the component as JSON, in the layout the given protocol version expects.
+    pub fn to_json(&self, version: &ProtocolVersion) -> Value {
+        let mut object = Map::new();
+        match &self.content {
+            TextContent::Text(text) => {
+                object.insert("text".to_string(), json!(text));
+            }
+            TextContent::Translate { key, with } => {
+                object.insert("translate".to_string(), json!(key));
+                if !with.is_empty() {
+                    let with: Vec<Value> = with.iter().map(|c| c.to_json(version)).collect();
+                    object.insert("with".to_string(), Value::Array(with));
+                }
+            }
+        }
+
+        if let Some(color) = &self.color {
+            object.insert("color".to_string(), json!(color));
+        }
+        for (name, value) in self.decorations() {
+            object.insert(name.to_string(), json!(value));
+        }
+
+        let names = EventNames::for_version(version);
+        if let Some(click_event) = &self.click_event {
+            let (action, key, value) = click_event.fields(version);
+            object.insert(
+                names.click_event.to_string(),
+                json!({ "action": action, key: value }),
+            );
+        }
+        if let Some(HoverEvent::ShowText(text)) = &self.hover_event {
+            let hover_event =
+                json!({ "action": "show_text", names.hover_text: text.to_json(version) });
+            object.insert(names.hover_event.to_string(), hover_event);
+        }
+
+        if !self.extra.is_empty() {
+            let extra: Vec<Value> = self.extra.iter().map(|c| c.to_json(version)).collect();
+            object.insert("extra".to_string(), Value::Array(extra));
+        }
+
+        Value::Object(object)
+    }
+
+    /// Encodes the component as network NBT: an unnamed root compound tag.
+    pub fn to_nbt(&self, version: &ProtocolVersion) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(TAG_COMPOUND);
+        self.write_nbt_fields(&mut buf, version);
+        buf
+    }
+
+    fn write_nbt_fields(&self, buf: &mut Vec<u8>, version: &ProtocolVersion) {
+        match &self.content {
+            TextContent::Text(text) => write_nbt_string(buf, "text", text),
+            TextContent::Translate { key, with } => {
+                write_nbt_string(buf, "translate", key);
+                if !with.is_empty() {
+                    write_nbt_compound_list(buf, "with", with, version);
+                }
+            }
+        }
+
+        if let Some(color) = &self.color {
+            write_nbt_string(buf, "color", color);
+        }
+        for (name, value) in self.decorations() {
+            write_nbt_name(buf, TAG_BYTE, name);
+            buf.push(value as u8);
+        }
+
+        let names = EventNames::for_version(version);
+        if let Some(click_event) = &self.click_event {
+            let (action, key, value) = click_event.fields(version);
+            write_nbt_name(buf, TAG_COMPOUND, names.click_event);
+            write_nbt_string(buf, "action", action);
+            match value {
+                Value::Number(page) => {
+                    write_nbt_name(buf, TAG_INT, key);
+                    buf.write_i32::<BigEndian>(page.as_i64().unwrap_or_default() as i32)
+                        .unwrap();
+                }
+                value => write_nbt_string(buf, key, value.as_str().unwrap_or_default()),
+            }
+            buf.push(TAG_END);
+        }
+        if let Some(HoverEvent::ShowText(text)) = &self.hover_event {
+            write_nbt_name(buf, TAG_COMPOUND, names.hover_event);
+            write_nbt_string(buf, "action", "show_text");
+            write_nbt_name(buf, TAG_COMPOUND, names.hover_text);
+            text.write_nbt_fields(buf, version);
+            buf.push(TAG_END);
+        }
+
+        if !self.extra.is_empty() {
+            write_nbt_compound_list(buf, "extra", &self.extra, version);
+        }
+
+        buf.push(TAG_END);
+    }
+
+    fn decorations(&self) -> Vec<(&'static str, bool)> {
+        [
+            ("bold", self.bold),
+            ("italic", self.italic),
+            ("underlined", self.underlined),
+            ("strikethrough", self.strikethrough),
+            ("obfuscated", self.obfuscated),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect()
+    }
+}
+
+impl From<&str> for TextComponent {
+    fn from(text: &str) -> Self {
+        TextComponent::text(text)
+    }
+}
+
+impl From<String> for TextComponent {
+    fn from(text: String) -> Self {
+        TextComponent::text(text)
+    }
+}
+
+impl ClickEvent {
+    // the action name, and the name and value of its argument
+    fn fields(&self, version: &ProtocolVersion) -> (&'static str, &'static str, Value) {
+        let (action, key, value) = match self {
+            ClickEvent::OpenUrl(url) => ("open_url", "url", json!(url)),
+            ClickEvent::RunCommand(command) => ("run_command", "command", json!(command)),
+            ClickEvent::SuggestCommand(command) => ("suggest_command", "command", json!(command)),
+            ClickEvent::CopyToClipboard(value) => ("copy_to_clipboard", "value", json!(value)),
+            ClickEvent::ChangePage(page) => ("change_page", "page", json!(page)),
+        };
+        if version.snake_case_text_events {
+            return (action, key, value);
+        }
+        // before 1.21.5 the argument was always a string named "value"
+        let value = match value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        (action, "value", json!(value))
+    }
+}
+
+// The keys events are written under, which were renamed in 1.21.5.
+struct EventNames {
+    click_event: &'static str,
+    hover_event: &'static str,
+    // the key of the text shown by a show_text hover event
+    hover_text: &'static str,
+}
+
+impl EventNames {
+    fn for_version(version: &ProtocolVersion) -> EventNames {
+        if version.snake_case_text_events {
+            EventNames {
+                click_event: "click_event",
+                hover_event: "hover_event",
+                hover_text: "value",
+            }
+        } else {
+            EventNames {
+                click_event: "clickEvent",
+                hover_event: "hoverEvent",
+                hover_text: "contents",
+            }
+        }
+    }
+}
+
+// The formatting that applies to a run of parsed text.
+#[derive(Debug, Clone, Default)]
+struct Style {
+    color: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underlined: Option<bool>,
+    strikethrough: Option<bool>,
+    obfuscated: Option<bool>,
+    click_event: Option<ClickEvent>,
+    hover_event: Option<HoverEvent>,
+}
+
+impl Style {
+    fn apply(&self, mut component: TextComponent) -> TextComponent {
+        component.color = self.color.clone();
+        component.bold = self.bold;
+        component.italic = self.italic;
+        component.underlined = self.underlined;
+        component.strikethrough = self.strikethrough;
+        component.obfuscated = self.obfuscated;
+        component.click_event = self.click_event.clone();
+        component.hover_event = self.hover_event.clone();
+        component
+    }
+
+    // Applies a MiniMessage tag to the style, returning false if the tag isn't a style tag.
+    fn update(&mut self, name: &str, args: &[String]) -> bool {
+        match name {
+            "bold" | "b" => self.bold = Some(true),
+            "italic" | "i" | "em" => self.italic = Some(true),
+            "underlined" | "u" => self.underlined = Some(true),
+            "strikethrough" | "st" => self.strikethrough = Some(true),
+            "obfuscated" | "obf" => self.obfuscated = Some(true),
+            "color" | "colour" | "c" if args.len() > 1 => match parse_color(&args[1]) {
+                Some(color) => self.color = Some(color),
+                None => return false,
+            },
+            "click" if args.len() > 2 => {
+                let value = args[2].clone();
+                self.click_event = Some(match args[1].as_str() {
+                    "open_url" => ClickEvent::OpenUrl(value),
+                    "run_command" => ClickEvent::RunCommand(value),
+                    "suggest_command" => ClickEvent::SuggestCommand(value),
+                    "copy_to_clipboard" => ClickEvent::CopyToClipboard(value),
+                    "change_page" => match value.parse() {
+                        Ok(page) => ClickEvent::ChangePage(page),
+                        Err(_) => return false,
+                    },
+                    _ => return false,
+                });
+            }
+            "hover" if args.len() > 2 && args[1] == "show_text" => {
+                let text = TextComponent::from_mini_message(&args[2]);
+                self.hover_event = Some(HoverEvent::ShowText(Box::new(text)));
+            }
+            name => match parse_color(name) {
+                Some(color) => self.color = Some(color),
+                None => return false,
+            },
+        }
+        true
+    }
+}
+
+fn parse_color(color: &str) -> Option<String> {
+    let color = color.to_ascii_lowercase();
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(format!("#{}", hex.to_ascii_uppercase()));
+        }
+        return None;
+    }
+
+    // MiniMessage accepts "grey" spellings
+    let color = color.replace("grey", "gray");
+    NAMED_COLORS.contains(&color.as_str()).then_some(color)
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.split(':')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+// Finds the '>' that closes the tag starting at the beginning of `input`, skipping over quoted
+// arguments.
+fn find_tag_end(input: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in input.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '<') => return None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Splits a tag into its ':' separated arguments, removing quotes.
+fn split_tag_args(tag: &str) -> Vec<String> {
+    let mut args = vec![String::new()];
+    let mut quote = None;
+    for c in tag.chars() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ':') => args.push(String::new()),
+            (_, c) => args.last_mut().unwrap().push(c),
+        }
+    }
+    args
+}
+
+const TAG_END: u8 = 0x00;
+const TAG_BYTE: u8 = 0x01;
+const TAG_INT: u8 = 0x03;
+const TAG_STRING: u8 = 0x08;
+const TAG_LIST: u8 = 0x09;
+const TAG_COMPOUND: u8 = 0x0A;
+
+fn write_nbt_name(buf: &mut Vec<u8>, tag: u8, name: &str) {
+    buf.push(tag);
+    write_nbt_string_payload(buf, name);
+}
+
+fn write_nbt_string(buf: &mut Vec<u8>, name: &str, value: &str) {
+    write_nbt_name(buf, TAG_STRING, name);
+    write_nbt_string_payload(buf, value);
+}
+
+fn write_nbt_string_payload(buf: &mut Vec<u8>, value: &str) {
+    let bytes = modified_utf8(value);
+    buf.write_u16::<BigEndian>(bytes.len() as u16).unwrap();
+    buf.extend_from_slice(&bytes);
+}
+
+fn write_nbt_compound_list(
+    buf: &mut Vec<u8>,
+    name: &str,
+    components: &[TextComponent],
+    version: &ProtocolVersion,
+) {
+    write_nbt_name(buf, TAG_LIST, name);
+    buf.push(TAG_COMPOUND);
+    buf.write_i32::<BigEndian>(components.len() as i32).unwrap();
+    for component in components {
+        component.write_nbt_fields(buf, version);
+    }
+}
+
+// NBT strings use Java's modified UTF-8: nulls are encoded as two bytes, and characters outside
+// the BMP as two three-byte surrogates.
+fn modified_utf8(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\0' => bytes.extend_from_slice(&[0xC0, 0x80]),
+            c if (c as u32) > 0xFFFF => {
+                let mut surrogates = [0u16; 2];
+                for surrogate in c.encode_utf16(&mut surrogates) {
+                    let surrogate = *surrogate as u32;
+                    bytes.push(0xE0 | (surrogate >> 12) as u8);
+                    bytes.push(0x80 | ((surrogate >> 6) & 0x3F) as u8);
+                    bytes.push(0x80 | (surrogate & 0x3F) as u8);
+                }
+            }
+            c => {
+                let mut utf8 = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_codes() {
+        let component = TextComponent::from_legacy("§6Gold §lBold§r plain §zkept");
+        assert_eq!(
+            component.to_json(ProtocolVersion::latest()),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "Gold ", "color": "gold" },
+                    { "text": "Bold", "color": "gold", "bold": true },
+                    { "text": " plain §zkept" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn legacy_color_resets_formatting() {
+        let component = TextComponent::from_legacy("§lbold§cred");
+        assert_eq!(component.extra[1].color.as_deref(), Some("red"));
+        assert_eq!(component.extra[1].bold, None);
+    }
+
+    #[test]
+    fn mini_message_tags() {
+        let component =
+            TextComponent::from_mini_message("<red>Red <b>bold</b></red> <#00ff00>green</#00ff00>");
+        assert_eq!(
+            component.to_json(ProtocolVersion::latest()),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "Red ", "color": "red" },
+                    { "text": "bold", "color": "red", "bold": true },
+                    { "text": " " },
+                    { "text": "green", "color": "#00FF00" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn mini_message_events() {
+        let component = TextComponent::from_mini_message(
+            "<click:open_url:'https://example.com'><hover:show_text:'<gray>Visit'>site",
+        );
+        assert_eq!(
+            component.extra[0].to_json(ProtocolVersion::latest()),
+            json!({
+                "text": "site",
+                "click_event": { "action": "open_url", "url": "https://example.com" },
+                "hover_event": {
+                    "action": "show_text",
+                    "value": { "text": "", "extra": [{ "text": "Visit", "color": "gray" }] },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn events_before_1_21_5() {
+        let component =
+            TextComponent::from_mini_message("<click:change_page:'2'><hover:show_text:'Next'>next");
+        let version = ProtocolVersion::get(769).unwrap();
+        let json = component.extra[0].to_json(version);
+        assert_eq!(
+            json,
+            json!({
+                "text": "next",
+                "clickEvent": { "action": "change_page", "value": "2" },
+                "hoverEvent": {
+                    "action": "show_text",
+                    "contents": { "text": "", "extra": [{ "text": "Next" }] },
+                },
+            })
+        );
+        assert_eq!(TextComponent::from_json(&json), component.extra[0]);
+    }
+
+    #[test]
+    fn mini_message_unknown_tags_are_text() {
+        let component = TextComponent::from_mini_message("a <unknown> b \\<red> c");
+        assert_eq!(component.to_plain(), "a <unknown> b <red> c");
+    }
+
+    #[test]
+    fn translate() {
+        let component = TextComponent::from_mini_message("<lang:multiplayer.disconnect.kicked>");
+        assert_eq!(
+            component.extra[0].to_json(ProtocolVersion::latest()),
+            json!({ "translate": "multiplayer.disconnect.kicked" })
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let component = TextComponent::from_mini_message(
+            "<gold>A <b>Minecraft</b> server <click:run_command:'/help'><hover:show_text:'<red>Help'>help",
+        );
+        assert_eq!(
+            TextComponent::from_json(&component.to_json(ProtocolVersion::latest())),
+            component
+        );
+    }
+
+    #[test]
+    fn json_legacy_forms() {
+        let json = json!([
+            "",
+            { "text": "Hi", "clickEvent": { "action": "open_url", "value": "https://example.com" } },
+        ]);
+        let component = TextComponent::from_json(&json);
+        assert_eq!(component.to_plain(), "Hi");
+        assert_eq!(
+            component.extra[0].click_event,
+            Some(ClickEvent::OpenUrl("https://example.com".to_string()))
+        );
+    }
+
+    #[test]
+    fn nbt() {
+        let mut component = TextComponent::text("hi");
+        component.bold = Some(true);
+
+        let mut expected = vec![TAG_COMPOUND];
+        expected.extend_from_slice(&[TAG_STRING, 0x00, 0x04]);
+        expected.extend_from_slice(b"text");
+        expected.extend_from_slice(&[0x00, 0x02]);
+        expected.extend_from_slice(b"hi");
+        expected.extend_from_slice(&[TAG_BYTE, 0x00, 0x04]);
+        expected.extend_from_slice(b"bold");
+        expected.push(0x01);
+        expected.push(TAG_END);
+
+        assert_eq!(component.to_nbt(ProtocolVersion::latest()), expected);
+    }
+
+    #[test]
+    fn nbt_modified_utf8() {
+        assert_eq!(modified_utf8("a\0"), vec![0x61, 0xC0, 0x80]);
+        assert_eq!(
+            modified_utf8("😀"),
+            vec![0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::connection::protocol::{ConnectionState, Message};
+use ConnectionState::{Configuration, Handshaking, Login, Status};
+
+/// Identifies a packet independently of its ID, which can change between protocol versions.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum PacketKind {
+    Handshake,
+    StatusRequest,
+    StatusResponse,
+    PingRequest,
+    PingResponse,
+    LoginStart,
+    LoginDisconnect,
+    EncryptionRequest,
+    EncryptionResponse,
+    SetCompression,
+    LoginSuccess,
+    LoginAcknowledged,
+    LoginPluginRequest,
+    LoginPluginResponse,
+    ClientInformation,
+    ServerboundPluginMessage,
+    AcknowledgeFinishConfiguration,
+    ServerboundKnownPacks,
+    Transfer,
+    ConfigurationDisconnect,
+    ClientboundKeepAlive,
+    ServerboundKeepAlive,
+}
+
+impl Message {
+    pub fn kind(&self) -> PacketKind {
+        match self {
+            Message::Handshake(_) => PacketKind::Handshake,
+            Message::StatusRequest(_) => PacketKind::StatusRequest,
+            Message::StatusResponse(_) => PacketKind::StatusResponse,
+            Message::PingRequest(_) => PacketKind::PingRequest,
+            Message::PingResponse(_) => PacketKind::PingResponse,
+            Message::LoginStart(_) => PacketKind::LoginStart,
+            Message::LoginDisconnect(_) => PacketKind::LoginDisconnect,
+            Message::EncryptionRequest(_) => PacketKind::EncryptionRequest,
+            Message::EncryptionResponse(_) => PacketKind::EncryptionResponse,
+            Message::SetCompression(_) => PacketKind::SetCompression,
+            Message::LoginSuccess(_) => PacketKind::LoginSuccess,
+            Message::LoginAcknowledged(_) => PacketKind::LoginAcknowledged,
+            Message::LoginPluginRequest(_) => PacketKind::LoginPluginRequest,
+            Message::LoginPluginResponse(_) => PacketKind::LoginPluginResponse,
+            Message::ClientInformation(_) => PacketKind::ClientInformation,
+            Message::ServerboundPluginMessage(_) => PacketKind::ServerboundPluginMessage,
+            Message::AcknowledgeFinishConfiguration(_) => {
+                PacketKind::AcknowledgeFinishConfiguration
+            }
+            Message::ServerboundKnownPacks(_) => PacketKind::ServerboundKnownPacks,
+            Message::Transfer(_) => PacketKind::Transfer,
+            Message::ConfigurationDisconnect(_) => PacketKind::ConfigurationDisconnect,
+            Message::ClientboundKeepAlive(_) => PacketKind::ClientboundKeepAlive,
+            Message::ServerboundKeepAlive(_) => PacketKind::ServerboundKeepAlive,
+        }
+    }
+}
+
+type PacketTable = &'static [(ConnectionState, i32, PacketKind)];
+
+// The packets the client sends. None of these have moved since 1.20.5, which is the oldest
+// version with Transfer. Packets added since then were appended after them.
+const SERVERBOUND_1_20_5: PacketTable = &[
+    (Handshaking, 0x00, PacketKind::Handshake),
+    (Status, 0x00, PacketKind::StatusRequest),
+    (Status, 0x01, PacketKind::PingRequest),
+    (Login, 0x00, PacketKind::LoginStart),
+    (Login, 0x01, PacketKind::EncryptionResponse),
+    (Login, 0x02, PacketKind::LoginPluginResponse),
+    (Login, 0x03, PacketKind::LoginAcknowledged),
+    (Configuration, 0x00, PacketKind::ClientInformation),
+    (Configuration, 0x02, PacketKind::ServerboundPluginMessage),
+    (Configuration, 0x03, PacketKind::AcknowledgeFinishConfiguration),
+    (Configuration, 0x04, PacketKind::ServerboundKeepAlive),
+    (Configuration, 0x07, PacketKind::ServerboundKnownPacks),
+];
+
+// The packets the server sends, which likewise haven't moved since 1.20.5.
+const CLIENTBOUND_1_20_5: PacketTable = &[
+    (Status, 0x00, PacketKind::StatusResponse),
+    (Status, 0x01, PacketKind::PingResponse),
+    (Login, 0x00, PacketKind::LoginDisconnect),
+    (Login, 0x01, PacketKind::EncryptionRequest),
+    (Login, 0x02, PacketKind::LoginSuccess),
+    (Login, 0x03, PacketKind::SetCompression),
+    (Login, 0x04, PacketKind::LoginPluginRequest),
+    (Configuration, 0x02, PacketKind::ConfigurationDisconnect),
+    (Configuration, 0x04, PacketKind::ClientboundKeepAlive),
+    (Configuration, 0x0B, PacketKind::Transfer),
+];
+
+/// The packet IDs and field layouts of one protocol version.
+#[derive(Debug)]
+pub struct ProtocolVersion {
+    pub protocol: i32,
+    // The oldest release using this protocol.
+    pub name: &'static str,
+    serverbound: PacketTable,
+    clientbound: PacketTable,
+    // Login Success ends with a "strict error handling" flag.
+    pub login_success_strict_error_handling: bool,
+    // Client Information ends with the particle status setting.
+    pub client_information_particle_status: bool,
+    // Text components spell their events click_event and hover_event, with named arguments,
+    // rather than clickEvent and hoverEvent.
+    pub snake_case_text_events: bool,
+}
+
+/// Every supported version, oldest first.
+pub const PROTOCOL_VERSIONS: &[ProtocolVersion] = &[
+    ProtocolVersion {
+        protocol: 766,
+        name: "1.20.5",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: true,
+        client_information_particle_status: false,
+        snake_case_text_events: false,
+    },
+    ProtocolVersion {
+        protocol: 767,
+        name: "1.21",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: true,
+        client_information_particle_status: false,
+        snake_case_text_events: false,
+    },
+    ProtocolVersion {
+        protocol: 768,
+        name: "1.21.2",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: false,
+        client_information_particle_status: true,
+        snake_case_text_events: false,
+    },
+    ProtocolVersion {
+        protocol: 769,
+        name: "1.21.4",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: false,
+        client_information_particle_status: true,
+        snake_case_text_events: false,
+    },
+    ProtocolVersion {
+        protocol: 770,
+        name: "1.21.5",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: false,
+        client_information_particle_status: true,
+        snake_case_text_events: true,
+    },
+    ProtocolVersion {
+        protocol: 771,
+        name: "1.21.6",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: false,
+        client_information_particle_status: true,
+        snake_case_text_events: true,
+    },
+    ProtocolVersion {
+        protocol: 772,
+        name: "1.21.7",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: false,
+        client_information_particle_status: true,
+        snake_case_text_events: true,
+    },
+    ProtocolVersion {
+        protocol: 773,
+        name: "1.21.9",
+        serverbound: SERVERBOUND_1_20_5,
+        clientbound: CLIENTBOUND_1_20_5,
+        login_success_strict_error_handling: false,
+        client_information_particle_status: true,
+        snake_case_text_events: true,
+    },
+];
+
+// The newest release of the latest protocol, for the server list.
+pub const LATEST_VERSION_NAME: &str = "1.21.10";
+
+impl ProtocolVersion {
+    pub fn get(protocol: i32) -> Option<&'static ProtocolVersion> {
+        PROTOCOL_VERSIONS
+            .iter()
+            .find(|version| version.protocol == protocol)
+    }
+
+    pub fn oldest() -> &'static ProtocolVersion {
+        &PROTOCOL_VERSIONS[0]
+    }
+
+    pub fn latest() -> &'static ProtocolVersion {
+        &PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]
+    }
+
+    /// The kind of packet a client sent with the given ID.
+    pub fn serverbound_kind(&self, state: ConnectionState, id: i32) -> Option<PacketKind> {
+        find_kind(self.serverbound, state, id)
+    }
+
+    /// The kind of packet a server sent with the given ID.
+    pub fn clientbound_kind(&self, state: ConnectionState, id: i32) -> Option<PacketKind> {
+        find_kind(self.clientbound, state, id)
+    }
+
+    /// The ID of a packet in either direction.
+    pub fn packet_id(&self, kind: PacketKind) -> Option<i32> {
+        self.serverbound
+            .iter()
+            .chain(self.clientbound.iter())
+            .find(|(_, _, packet_kind)| *packet_kind == kind)
+            .map(|(_, id, _)| *id)
+    }
+}
+
+fn find_kind(table: PacketTable, state: ConnectionState, id: i32) -> Option<PacketKind> {
+    table
+        .iter()
+        .find(|(packet_state, packet_id, _)| *packet_state == state && *packet_id == id)
+        .map(|(_, _, kind)| *kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        assert_eq!(ProtocolVersion::get(766).unwrap().name, "1.20.5");
+        assert_eq!(ProtocolVersion::get(765).map(|version| version.name), None);
+        assert_eq!(ProtocolVersion::latest().protocol, 773);
+        assert_eq!(ProtocolVersion::oldest().protocol, 766);
+    }
+
+    #[test]
+    fn ids_depend_on_state() {
+        let version = ProtocolVersion::latest();
+        assert_eq!(
+            version.serverbound_kind(ConnectionState::Login, 0x00),
+            Some(PacketKind::LoginStart)
+        );
+        assert_eq!(
+            version.serverbound_kind(ConnectionState::Configuration, 0x00),
+            Some(PacketKind::ClientInformation)
+        );
+        assert_eq!(version.serverbound_kind(ConnectionState::Play, 0x00), None);
+        assert_eq!(version.packet_id(PacketKind::Transfer), Some(0x0B));
+    }
+
+    #[test]
+    fn ordered_and_unique() {
+        for versions in PROTOCOL_VERSIONS.windows(2) {
+            assert!(versions[0].protocol < versions[1].protocol);
+        }
+    }
+}
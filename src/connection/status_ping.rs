@@ -0,0 +1,167 @@
+use crate::connection::codec::{VarInt, read_frame};
+use crate::connection::protocol::{
+    ConnectionState, Handshake, HandshakeIntent, Message, Packet, PacketKind, ProtocolVersion,
+    StatusRequest, StatusResponse,
+};
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+// How long to wait for the server to answer before giving up on it.
+const PING_TIMEOUT: u64 = 3;
+
+/// Asks a server for its status, the same way a client filling in the server list does.
+pub async fn ping_status(hostname: &str, port: u16) -> io::Result<StatusResponse> {
+    // Servers answer status requests from any version.
+    let version = ProtocolVersion::latest();
+    let ping = async {
+        let mut stream = TcpStream::connect((hostname, port)).await?;
+
+        let handshake = Handshake {
+            protocol_version: version.protocol,
+            server_address: hostname.to_string(),
+            server_port: port,
+            intent: HandshakeIntent::Status,
+        };
+        Packet::new(Message::Handshake(handshake))
+            .write_to(&mut stream, None, version)
+            .await?;
+        Packet::new(Message::StatusRequest(StatusRequest {}))
+            .write_to(&mut stream, None, version)
+            .await?;
+
+        let body = read_frame(&mut stream, None).await?;
+        let reader = &mut Cursor::new(body);
+        let id = i32::from_var_int(reader).await?;
+        if version.clientbound_kind(ConnectionState::Status, id) != Some(PacketKind::StatusResponse)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected a status response, got packet {:X}", id),
+            ));
+        }
+        StatusResponse::read_from(reader).await
+    };
+
+    timeout(Duration::from_secs(PING_TIMEOUT), ping)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Status ping timed out"))?
+}
+
+/// Holds the last status response from a server for a short time, so that every client
+/// refreshing its server list doesn't cause another ping. A failed ping is held too, as `None`,
+/// so a server that isn't answering isn't pinged again by every client either.
+pub struct StatusCache {
+    ttl: Duration,
+    cached: Mutex<Option<(Instant, Option<StatusResponse>)>>,
+}
+
+impl StatusCache {
+    pub fn new(ttl: Duration) -> Self {
+        StatusCache {
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached result if it hasn't expired.
+    pub fn get(&self) -> Option<Option<StatusResponse>> {
+        let cached = self.cached.lock().unwrap();
+        match &*cached {
+            Some((time, response)) if time.elapsed() < self.ttl => Some(response.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn set(&self, response: Option<StatusResponse>) {
+        *self.cached.lock().unwrap() = Some((Instant::now(), response));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::protocol::TextComponent;
+    use tokio::net::TcpListener;
+
+    // Answers a single status request the way a server would.
+    async fn stub_server() -> (u16, tokio::task::JoinHandle<Handshake>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let version = ProtocolVersion::latest();
+            let packet =
+                Packet::read_from(&mut stream, ConnectionState::Handshaking, None, version)
+                    .await
+                    .unwrap();
+            let Message::Handshake(handshake) = packet.message else {
+                panic!("expected a handshake");
+            };
+            let packet = Packet::read_from(&mut stream, ConnectionState::Status, None, version)
+                .await
+                .unwrap();
+            assert!(matches!(packet.message, Message::StatusRequest(_)));
+
+            let response = StatusResponse {
+                version_name: "Paper 1.21.10".to_string(),
+                version_protocol: 773,
+                max_players: 50,
+                online_players: 7,
+                player_samples: Vec::new(),
+                description: TextComponent::text("The real server"),
+                favicon: None,
+            };
+            Packet::new(Message::StatusResponse(response))
+                .write_to(&mut stream, None, version)
+                .await
+                .unwrap();
+            handshake
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn ping() -> Result<(), io::Error> {
+        let (port, handshake) = stub_server().await;
+
+        let response = ping_status("127.0.0.1", port).await?;
+        assert_eq!(response.version_name, "Paper 1.21.10");
+        assert_eq!(response.online_players, 7);
+        assert_eq!(response.description.to_plain(), "The real server");
+
+        let handshake = handshake.await.unwrap();
+        assert_eq!(handshake.intent, HandshakeIntent::Status);
+        assert_eq!(handshake.server_port, port);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_expiry() {
+        let response = StatusResponse {
+            version_name: "1.21.10".to_string(),
+            version_protocol: 773,
+            max_players: 20,
+            online_players: 0,
+            player_samples: Vec::new(),
+            description: TextComponent::default(),
+            favicon: None,
+        };
+
+        let cache = StatusCache::new(Duration::from_secs(60));
+        assert!(cache.get().is_none());
+        cache.set(Some(response.clone()));
+        assert!(matches!(cache.get(), Some(Some(_))));
+        // failures are remembered too
+        cache.set(None);
+        assert!(matches!(cache.get(), Some(None)));
+
+        let cache = StatusCache::new(Duration::ZERO);
+        cache.set(Some(response));
+        assert!(cache.get().is_none());
+    }
+}
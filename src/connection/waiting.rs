@@ -0,0 +1,81 @@
+use crate::connection::protocol::PlayerSample;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+// The vanilla server never lists more than this many players in the server list.
+const MAX_PLAYER_SAMPLES: usize = 12;
+
+/// The players being held by the receptionist while the server starts, so they can be shown in
+/// the server list.
+#[derive(Default)]
+pub struct WaitingPlayers {
+    players: Mutex<Vec<PlayerSample>>,
+}
+
+impl WaitingPlayers {
+    /// Adds a player, who is removed again when the returned guard is dropped.
+    pub fn add(self: &Arc<Self>, name: String, id: Uuid) -> WaitingPlayer {
+        self.players.lock().unwrap().push(PlayerSample { name, id });
+        WaitingPlayer {
+            waiting_players: self.clone(),
+            id,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    /// The players who have been waiting the longest, as many as fit in the server list.
+    pub fn samples(&self) -> Vec<PlayerSample> {
+        let players = self.players.lock().unwrap();
+        players.iter().take(MAX_PLAYER_SAMPLES).cloned().collect()
+    }
+}
+
+pub struct WaitingPlayer {
+    waiting_players: Arc<WaitingPlayers>,
+    id: Uuid,
+}
+
+impl Drop for WaitingPlayer {
+    fn drop(&mut self) {
+        let mut players = self.waiting_players.players.lock().unwrap();
+        // The same player may be waiting on more than one connection, so only remove one entry.
+        if let Some(index) = players.iter().position(|player| player.id == self.id) {
+            players.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_on_drop() {
+        let waiting_players = Arc::new(WaitingPlayers::default());
+        let notch = waiting_players.add("Notch".to_string(), Uuid::from_u128(1));
+        let jeb = waiting_players.add("jeb_".to_string(), Uuid::from_u128(2));
+        assert_eq!(waiting_players.count(), 2);
+
+        drop(notch);
+        let samples = waiting_players.samples();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].name, "jeb_");
+
+        drop(jeb);
+        assert_eq!(waiting_players.count(), 0);
+    }
+
+    #[test]
+    fn samples_limit() {
+        let waiting_players = Arc::new(WaitingPlayers::default());
+        let _players: Vec<WaitingPlayer> = (0..20)
+            .map(|i| waiting_players.add(format!("player{}", i), Uuid::from_u128(i)))
+            .collect();
+        assert_eq!(waiting_players.count(), 20);
+        assert_eq!(waiting_players.samples().len(), MAX_PLAYER_SAMPLES);
+        assert_eq!(waiting_players.samples()[0].name, "player0");
+    }
+}
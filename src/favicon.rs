@@ -0,0 +1,130 @@
+use crate::config::FaviconConfig;
+use crate::connection::ServerState;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const FAVICON_SIZE: u32 = 64;
+
+/// Server list icons, as `data:image/png;base64,` URIs ready to send in a status response.
+#[derive(Debug, Default)]
+pub struct Favicons {
+    default: Option<String>,
+    sleeping: Option<String>,
+    starting: Option<String>,
+    online: Option<String>,
+}
+
+impl Favicons {
+    pub fn load(config: &FaviconConfig) -> io::Result<Favicons> {
+        let load = |path: &Option<String>| path.as_deref().map(load_favicon).transpose();
+
+        Ok(Favicons {
+            default: load(&config.default)?,
+            sleeping: load(&config.sleeping)?,
+            starting: load(&config.starting)?,
+            online: load(&config.online)?,
+        })
+    }
+
+    /// Returns the icon for the given server state, falling back to the default icon.
+    pub fn for_state(&self, state: Option<ServerState>) -> Option<String> {
+        let favicon = match state {
+            Some(ServerState::Sleeping) => &self.sleeping,
+            Some(ServerState::Starting { .. }) => &self.starting,
+            Some(ServerState::Online) => &self.online,
+            Some(ServerState::Stopping | ServerState::Unavailable) | None => &None,
+        };
+        favicon.as_ref().or(self.default.as_ref()).cloned()
+    }
+}
+
+fn load_favicon(path: &str) -> io::Result<String> {
+    let png = fs::read(Path::new(path))
+        .map_err(|e| io::Error::new(e.kind(), format!("Unable to read favicon {}: {}", path, e)))?;
+    to_data_uri(&png).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+/// Checks that the bytes are a 64x64 PNG and encodes them as a data URI.
+pub fn to_data_uri(png: &[u8]) -> io::Result<String> {
+    // The IHDR chunk always comes first: the signature, then the chunk length and type, then the
+    // width and height.
+    if png.len() < 24 || png[0..8] != PNG_SIGNATURE || &png[12..16] != b"IHDR" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "favicon is not a PNG",
+        ));
+    }
+
+    let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+    if width != FAVICON_SIZE || height != FAVICON_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "favicon must be {}x{}, not {}x{}",
+                FAVICON_SIZE, FAVICON_SIZE, width, height
+            ),
+        ));
+    }
+
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&13u32.to_be_bytes());
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        // bit depth, color type, compression, filter and interlace
+        png.extend_from_slice(&[8, 6, 0, 0, 0]);
+        png
+    }
+
+    #[test]
+    fn data_uri() -> Result<(), io::Error> {
+        let png = png_header(64, 64);
+        let uri = to_data_uri(&png)?;
+        assert!(uri.starts_with("data:image/png;base64,iVBORw0KGgo"));
+        assert_eq!(STANDARD.decode(&uri[22..]).unwrap(), png);
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_size() {
+        let result = to_data_uri(&png_header(128, 128));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn not_png() {
+        let result = to_data_uri(b"GIF89a@\x00@\x00 not a png at all");
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn state_fallback() {
+        let favicons = Favicons {
+            default: Some("default".to_string()),
+            sleeping: Some("sleeping".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            favicons.for_state(Some(ServerState::Sleeping)).as_deref(),
+            Some("sleeping")
+        );
+        assert_eq!(
+            favicons.for_state(Some(ServerState::Online)).as_deref(),
+            Some("default")
+        );
+        assert_eq!(favicons.for_state(None).as_deref(), Some("default"));
+    }
+}
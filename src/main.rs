@@ -1,14 +1,19 @@
+mod backend;
 mod connection;
 mod receptionist;
 mod util;
 mod config;
+mod favicon;
+mod session;
+mod proxy_protocol;
 
 use std::error::Error;
 use std::fs;
 use receptionist::Receptionist;
-use tokio::io;
+use tokio::signal;
+use tokio::signal::unix::SignalKind;
 use crate::config::ReceptionistConfig;
-use toml;
+use crate::favicon::Favicons;
 
 const CONFIG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml");
 
@@ -16,13 +21,27 @@ const CONFIG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml");
 async fn main() -> Result<(), Box<dyn Error>> {
     println!("{}", CONFIG_PATH);
     let config: ReceptionistConfig = toml::from_str(fs::read_to_string(CONFIG_PATH)?.as_str())?;
+    let favicons = Favicons::load(&config.favicon)?;
 
-    let receptionist = Receptionist::new(
-        config.target_instance_name,
-        config.mc_target_port,
-        config.mc_target_motd,
-    ).await;
-    receptionist.listen("0.0.0.0:25565").await?;
+    let receptionist = Receptionist::new(config, favicons).await?;
+    let backend = receptionist.backend();
+    tokio::select! {
+        result = receptionist.listen("0.0.0.0:25565") => result?,
+        result = shutdown_signal() => {
+            result?;
+            println!("shutting down...");
+            backend.shutdown().await?;
+        }
+    }
 
     Ok(())
 }
+
+// Resolves on ctrl-c, or when asked to stop by a service manager.
+async fn shutdown_signal() -> std::io::Result<()> {
+    let mut terminate = signal::unix::signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
@@ -0,0 +1,180 @@
+use std::io;
+use std::io::ErrorKind::InvalidData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+// Including the CRLF, as set by the spec.
+const V1_MAX_LENGTH: usize = 107;
+
+const V2_VERSION: u8 = 0x2;
+const V2_COMMAND_LOCAL: u8 = 0x0;
+const V2_COMMAND_PROXY: u8 = 0x1;
+const V2_TCP_OVER_IPV4: u8 = 0x11;
+const V2_TCP_OVER_IPV6: u8 = 0x21;
+
+/// Reads the PROXY protocol header that a load balancer sends ahead of the client's own bytes,
+/// leaving the stream at the start of the client's data. Returns the client's address, or None if
+/// the load balancer made the connection on its own behalf, such as for a health check.
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<Option<SocketAddr>> {
+    // Long enough to tell the versions apart, and shorter than the shortest v1 header.
+    let mut start = [0u8; 12];
+    reader.read_exact(&mut start).await?;
+
+    if &start == V2_SIGNATURE {
+        read_v2(reader).await
+    } else if start.starts_with(V1_PREFIX) {
+        read_v1(reader, &start).await
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+// e.g. "PROXY TCP4 203.0.113.7 10.0.0.5 51234 25565\r\n"
+async fn read_v1<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    start: &[u8],
+) -> io::Result<Option<SocketAddr>> {
+    // The header has no length, so read up to the line ending a byte at a time to avoid
+    // consuming anything after it.
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(invalid("PROXY protocol v1 header too long"));
+        }
+        line.push(reader.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("invalid PROXY protocol v1 header"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        [
+            "PROXY",
+            "TCP4" | "TCP6",
+            source,
+            _destination,
+            source_port,
+            _destination_port,
+        ] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| invalid("invalid PROXY protocol v1 source address"))?;
+            let port: u16 = source_port
+                .parse()
+                .map_err(|_| invalid("invalid PROXY protocol v1 source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("invalid PROXY protocol v1 header")),
+    }
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<SocketAddr>> {
+    let version_command = reader.read_u8().await?;
+    let family = reader.read_u8().await?;
+    let len = reader.read_u16().await? as usize;
+    // The addresses come first, followed by TLVs that aren't needed here.
+    let mut data = vec![0u8; len];
+    reader.read_exact(&mut data).await?;
+
+    if version_command >> 4 != V2_VERSION {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    match version_command & 0x0F {
+        V2_COMMAND_LOCAL => return Ok(None),
+        V2_COMMAND_PROXY => {}
+        _ => return Err(invalid("unsupported PROXY protocol command")),
+    }
+
+    match family {
+        V2_TCP_OVER_IPV4 if len >= 12 => {
+            let ip: [u8; 4] = data[0..4].try_into().unwrap();
+            let port = u16::from_be_bytes([data[8], data[9]]);
+            Ok(Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port)))
+        }
+        V2_TCP_OVER_IPV6 if len >= 36 => {
+            let ip: [u8; 16] = data[0..16].try_into().unwrap();
+            let port = u16::from_be_bytes([data[32], data[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port)))
+        }
+        V2_TCP_OVER_IPV4 | V2_TCP_OVER_IPV6 => {
+            Err(invalid("PROXY protocol v2 addresses truncated"))
+        }
+        // UDP or unix sockets, which a Minecraft client can't be connecting over
+        _ => Ok(None),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(bytes: &[u8]) -> (io::Result<Option<SocketAddr>>, Vec<u8>) {
+        let mut reader = bytes;
+        let result = read_proxy_header(&mut reader).await;
+        (result, reader.to_vec())
+    }
+
+    #[tokio::test]
+    async fn v1() {
+        let (result, rest) = read(b"PROXY TCP4 203.0.113.7 10.0.0.5 51234 25565\r\n\x10\x00").await;
+        assert_eq!(result.unwrap(), Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(rest, b"\x10\x00");
+
+        let (result, _) = read(b"PROXY TCP6 2001:db8::7 2001:db8::5 51234 25565\r\n").await;
+        assert_eq!(
+            result.unwrap(),
+            Some("[2001:db8::7]:51234".parse().unwrap())
+        );
+
+        let (result, rest) = read(b"PROXY UNKNOWN\r\n\x10").await;
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(rest, b"\x10");
+
+        let (result, _) = read(b"PROXY TCP4 nonsense\r\n").await;
+        assert!(result.is_err());
+        let (result, _) = read(&[b"PROXY ".as_slice(), &[b'1'; 200]].concat()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, V2_TCP_OVER_IPV4, 0x00, 0x0F]);
+        header.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 5]);
+        header.extend_from_slice(&51234u16.to_be_bytes());
+        header.extend_from_slice(&25565u16.to_be_bytes());
+        // a TLV, which is skipped
+        header.extend_from_slice(&[0x04, 0x00, 0x00]);
+        header.extend_from_slice(b"\x10\x00");
+
+        let (result, rest) = read(&header).await;
+        assert_eq!(result.unwrap(), Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(rest, b"\x10\x00");
+    }
+
+    #[tokio::test]
+    async fn v2_local() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        let (result, rest) = read(&header).await;
+        assert_eq!(result.unwrap(), None);
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_header() {
+        // a Minecraft handshake straight from the client
+        let (result, _) = read(b"\x10\x00\xfa\x05\x09localhost\x63\xdd\x02").await;
+        assert!(result.is_err());
+    }
+}
@@ -1,129 +1,215 @@
 use async_trait::async_trait;
-use aws_config::BehaviorVersion;
-use aws_sdk_ec2::config::http::HttpResponse;
-use aws_sdk_ec2::operation::describe_instances::DescribeInstancesOutput;
-use aws_sdk_ec2::operation::start_instances::{StartInstancesError, StartInstancesOutput};
-use aws_sdk_ec2::types::{Filter, Instance, InstanceState, InstanceStateName};
-use crate::connection::{Connection, TransferHandler, LoginStart, Transfer};
+use crate::backend::{BackendState, ServerBackend};
+use crate::config::ReceptionistConfig;
+use crate::connection::{
+    ping_status, Connection, ConnectionOptions, JoinMode, LoginStart, ServerState, StatusCache,
+    StatusResponse, TextComponent, Transfer, TransferHandler, WaitingPlayers,
+};
+use crate::favicon::Favicons;
+use crate::proxy_protocol::read_proxy_header;
+use crate::session::SessionServer;
+use crate::backend;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::io;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpListener;
+use tokio::time::timeout;
 
+// How long a load balancer has to send the PROXY protocol header after connecting.
+const PROXY_HEADER_TIMEOUT: u64 = 5;
+
+// When the backend's state was last fetched, and the state if it could be.
+type CachedState = Option<(Instant, Option<BackendState>)>;
+
+/// Starts the server on whichever backend it runs on when players join, and sends them on once
+/// it's running.
 #[derive(Clone)]
-struct InstanceManager {
-    instance_name: String,
-    mc_target_port: u16,
-    ec2: aws_sdk_ec2::Client,
+struct BackendManager {
+    backend: Arc<dyn ServerBackend>,
+    status_cache: Arc<StatusCache>,
+    // The state shown in the server list, reused for as long as the status so that refreshing
+    // server lists don't each ask the backend.
+    state_cache: Arc<Mutex<CachedState>>,
+    status_cache_ttl: Duration,
+    join_mode: JoinMode,
 }
 
-impl InstanceManager {
-    pub async fn new(
-        instance_name: String,
-        mc_target_port: u16,
-    ) -> InstanceManager {
-        let config = aws_config::defaults(BehaviorVersion::latest())
-            .load()
-            .await;
-        let ec2 = aws_sdk_ec2::Client::new(&config);
-
-        InstanceManager {
-            instance_name,
-            mc_target_port,
-            ec2,
+impl BackendManager {
+    pub fn new(
+        backend: Arc<dyn ServerBackend>,
+        status_cache_ttl: Duration,
+        join_mode: JoinMode,
+    ) -> BackendManager {
+        BackendManager {
+            backend,
+            status_cache: Arc::new(StatusCache::new(status_cache_ttl)),
+            state_cache: Arc::new(Mutex::new(None)),
+            status_cache_ttl,
+            join_mode,
+        }
+    }
+
+    async fn cached_state(&self) -> Option<BackendState> {
+        if let Some((fetched, state)) = &*self.state_cache.lock().unwrap()
+            && fetched.elapsed() < self.status_cache_ttl
+        {
+            return state.clone();
         }
+        let state = self.backend.state().await.ok();
+        *self.state_cache.lock().unwrap() = Some((Instant::now(), state.clone()));
+        state
     }
 
-    pub async fn describe_instance(&self) -> Result<Instance, aws_sdk_ec2::Error> {
-        let filter = Filter::builder()
-            .name("tag:Name")
-            .values(&self.instance_name)
-            .build();
-        let description = self.ec2.describe_instances().filters(filter).send().await?;
-        let reservation = description.reservations().get(0).unwrap();
-        let instance = reservation.instances().get(0).unwrap();
-        Ok(instance.clone())
+    async fn get_transfer(&self) -> Option<Transfer> {
+        match self.backend.resolve().await {
+            Ok(Some(transfer)) => Some(transfer),
+            Ok(None) => {
+                println!("Unable to get Transfer: server not running.");
+                None
+            }
+            Err(e) => {
+                println!("unable to resolve server address: {}", e);
+                None
+            }
+        }
     }
 
-    async fn get_public_ip(instance: &Instance) -> Option<String> {
-        for network_interface in instance.network_interfaces() {
-            if let Some(association) = network_interface.association() {
-                if let Some(public_ip) = association.public_ip() {
-                    return Some(public_ip.to_string());
+    async fn try_start(&self, state: BackendState) -> Result<(), TextComponent> {
+        match state {
+            BackendState::Stopped => {
+                if let Err(e) = self.backend.start().await {
+                    println!("unable to start server: {}", e);
+                    return Err("Unable to start the server, try again later".into());
                 }
+                Ok(())
+            }
+            // already on its way up
+            BackendState::Starting { .. } | BackendState::Running => Ok(()),
+            BackendState::Stopping => {
+                Err("Server is shutting down, try again in a minute".into())
+            }
+            BackendState::Unavailable(reason) => {
+                println!("unable to start server in state {}", reason);
+                Err(format!("Server is unavailable ({})", reason).into())
             }
         }
+    }
+}
 
-        None
+#[async_trait]
+impl TransferHandler for BackendManager {
+    async fn join_mode(&self, _protocol_version: i32) -> JoinMode {
+        self.join_mode
     }
 
-    async fn get_transfer(&self, instance: &Instance) -> Option<Transfer> {
-        let state = instance.state().unwrap().name().unwrap();
-        if let InstanceStateName::Running = state {
-            let public_ip = InstanceManager::get_public_ip(instance).await.unwrap();
-            return Some(Transfer {
-                hostname: public_ip,
-                port: self.mc_target_port,
-            });
+    async fn on_join(
+        &self,
+        login_start: &LoginStart,
+        client_addr: SocketAddr,
+    ) -> Result<Option<Transfer>, TextComponent> {
+        println!("{} joined from {}!", login_start.username, client_addr);
+
+        let state = self.backend.state().await.map_err(|e| {
+            println!("unable to get server state: {}", e);
+            TextComponent::from("Unable to reach the server, try again later")
+        })?;
+        if state == BackendState::Running
+            && let Some(transfer) = self.get_transfer().await
+        {
+            return Ok(Some(transfer));
         }
+        self.try_start(state).await?;
+        // the server list should show it starting straight away
+        *self.state_cache.lock().unwrap() = None;
+        Ok(None)
+    }
 
-        println!("Unable to get Transfer: instance not running.");
-        None
+    async fn on_transfer_ready(&self) -> Option<Transfer> {
+        self.backend.resolve().await.ok()?
     }
 
-    async fn try_launch_instance(&self, instance: &Instance) {
-        let state = instance.state().unwrap().name().unwrap();
-        if let InstanceStateName::Stopped = state {
-            let instance_id = instance.instance_id().unwrap();
-            println!("launching {}...", instance_id);
-            if let Err(e) = self.ec2.start_instances().instance_ids(instance_id).send().await {
-                println!("unable to start instance: {}", e);
-            }
-        } else {
-            println!("unable to start instance in state {}", state);
+    async fn server_state(&self) -> Option<ServerState> {
+        match self.cached_state().await? {
+            BackendState::Running => Some(ServerState::Online),
+            BackendState::Starting { eta } => Some(ServerState::Starting { eta }),
+            BackendState::Stopped => Some(ServerState::Sleeping),
+            BackendState::Stopping => Some(ServerState::Stopping),
+            BackendState::Unavailable(_) => Some(ServerState::Unavailable),
         }
     }
-}
 
-#[async_trait]
-impl TransferHandler for InstanceManager {
-    async fn on_join(&self, login_start: &LoginStart) -> Option<Transfer> {
-        println!("{} joined!", login_start.username);
-
-        let instance = self.describe_instance().await.unwrap();
-        if let Some(transfer) = self.get_transfer(&instance).await {
-            Some(transfer)
-        } else {
-            self.try_launch_instance(&instance).await;
-            None
+    async fn backend_status(&self) -> Option<StatusResponse> {
+        if let Some(response) = self.status_cache.get() {
+            return response;
         }
+
+        let response = match self.backend.resolve().await {
+            Ok(Some(transfer)) => match ping_status(&transfer.hostname, transfer.port).await {
+                Ok(response) => Some(response),
+                Err(e) => {
+                    // the machine is up but the server isn't listening yet
+                    println!("unable to ping server status: {}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        self.status_cache.set(response.clone());
+        response
     }
 
-    async fn on_transfer_ready(&self) -> Option<Transfer> {
-        let instance = self.describe_instance().await.unwrap();
-        self.get_transfer(&instance).await
+    async fn not_ready_reason(&self) -> TextComponent {
+        let eta = match self.backend.state().await {
+            Ok(BackendState::Starting { eta: Some(eta) }) => eta,
+            _ => return TextComponent::text("Server is starting, try again in a few minutes"),
+        };
+        match eta.as_secs().div_ceil(60) {
+            0 | 1 => TextComponent::text("Server is starting, try again in a minute"),
+            minutes => {
+                TextComponent::text(format!("Server is starting, try again in ~{} minutes", minutes))
+            }
+        }
     }
 }
 
 pub struct Receptionist {
-    instance_manager: InstanceManager,
-    motd: String,
+    backend_manager: BackendManager,
+    options: Arc<ConnectionOptions>,
+    waiting_players: Arc<WaitingPlayers>,
+    proxy_protocol: bool,
 }
 
 impl Receptionist {
-    pub async fn new(
-        target_instance_name: String,
-        mc_target_port: u16,
-        mc_target_motd: String,
-    ) -> Receptionist {
-        let instance_manager = InstanceManager::new(
-            target_instance_name,
-            mc_target_port,
-        ).await;
-        instance_manager.describe_instance().await.unwrap();
-
-        Receptionist {
-            instance_manager,
-            motd: mc_target_motd,
-        }
+    pub async fn new(config: ReceptionistConfig, favicons: Favicons) -> io::Result<Receptionist> {
+        let status_cache_ttl = Duration::from_secs(config.status_cache_ttl);
+        let backend =
+            backend::from_config(config.backend()?, config.mc_target_port, status_cache_ttl).await;
+        backend.state().await?;
+        let backend_manager = BackendManager::new(backend, status_cache_ttl, config.join_mode);
+
+        let options = ConnectionOptions {
+            motd: TextComponent::parse(&config.mc_target_motd),
+            // a negative threshold turns compression off, as in server.properties
+            compression_threshold: config
+                .compression_threshold
+                .filter(|threshold| *threshold >= 0),
+            stall: config.stall,
+            session_server: SessionServer::new(config.session_server_url),
+            favicons,
+            forwarding: config.forwarding,
+        };
+
+        Ok(Receptionist {
+            backend_manager,
+            options: Arc::new(options),
+            waiting_players: Arc::new(WaitingPlayers::default()),
+            proxy_protocol: config.proxy_protocol,
+        })
+    }
+
+    pub fn backend(&self) -> Arc<dyn ServerBackend> {
+        self.backend_manager.backend.clone()
     }
 
     pub async fn listen(self, addr: &str) -> io::Result<()> {
@@ -131,17 +217,39 @@ impl Receptionist {
         println!("Listening on: {}", addr);
 
         loop {
-            let (stream, addr) = listener.accept().await?;
-            println!("Accepted connection from: {}", &addr);
+            let (mut stream, peer_addr) = listener.accept().await?;
 
-            let instance_manager = self.instance_manager.clone();
-            let motd = self.motd.clone();
+            let backend_manager = self.backend_manager.clone();
+            let options = self.options.clone();
+            let waiting_players = self.waiting_players.clone();
+            let proxy_protocol = self.proxy_protocol;
             tokio::spawn(async move {
-                let mut connection = Connection::new(
-                    stream,
-                    instance_manager,
-                    motd,
-                );
+                let addr = if proxy_protocol {
+                    let header = timeout(
+                        Duration::from_secs(PROXY_HEADER_TIMEOUT),
+                        read_proxy_header(&mut stream),
+                    )
+                    .await;
+                    match header {
+                        Ok(Ok(Some(addr))) => addr,
+                        // the load balancer's own health check
+                        Ok(Ok(None)) => peer_addr,
+                        Ok(Err(e)) => {
+                            eprintln!("bad PROXY protocol header from {}: {}", peer_addr, e);
+                            return;
+                        }
+                        Err(_) => {
+                            eprintln!("no PROXY protocol header from {}", peer_addr);
+                            return;
+                        }
+                    }
+                } else {
+                    peer_addr
+                };
+                println!("Accepted connection from: {}", &addr);
+
+                let connection =
+                    Connection::new(stream, addr, backend_manager, options, waiting_players);
                 match connection.process().await {
                     Ok(_) => {}
                     Err(e) => {
@@ -154,3 +262,134 @@ impl Receptionist {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    struct FakeBackend {
+        state: Mutex<BackendState>,
+        resolves: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ServerBackend for FakeBackend {
+        async fn state(&self) -> io::Result<BackendState> {
+            Ok(self.state.lock().unwrap().clone())
+        }
+
+        async fn start(&self) -> io::Result<()> {
+            *self.state.lock().unwrap() = BackendState::Starting { eta: None };
+            Ok(())
+        }
+
+        async fn stop(&self) -> io::Result<()> {
+            *self.state.lock().unwrap() = BackendState::Stopping;
+            Ok(())
+        }
+
+        async fn resolve(&self) -> io::Result<Option<Transfer>> {
+            self.resolves.fetch_add(1, Ordering::Relaxed);
+            Ok(match *self.state.lock().unwrap() {
+                BackendState::Running => Some(Transfer {
+                    hostname: "10.0.0.5".to_string(),
+                    port: 25565,
+                }),
+                _ => None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn starts_backend_on_join() {
+        let backend = Arc::new(FakeBackend {
+            state: Mutex::new(BackendState::Stopped),
+            resolves: AtomicUsize::new(0),
+        });
+        let manager = BackendManager::new(backend.clone(), Duration::ZERO, JoinMode::Transfer);
+        let login_start = LoginStart {
+            username: "Notch".to_string(),
+            uuid: Uuid::from_u128(1),
+        };
+        let client_addr = "203.0.113.7:51234".parse().unwrap();
+
+        assert!(matches!(manager.on_join(&login_start, client_addr).await, Ok(None)));
+        assert_eq!(
+            manager.server_state().await,
+            Some(ServerState::Starting { eta: None })
+        );
+        assert!(manager.on_transfer_ready().await.is_none());
+
+        *backend.state.lock().unwrap() = BackendState::Running;
+        let transfer = manager.on_join(&login_start, client_addr).await.unwrap();
+        assert_eq!(transfer.map(|transfer| transfer.hostname), Some("10.0.0.5".to_string()));
+
+        *backend.state.lock().unwrap() = BackendState::Unavailable("terminated".to_string());
+        assert_eq!(manager.server_state().await, Some(ServerState::Unavailable));
+        assert!(manager.on_join(&login_start, client_addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn caches_server_state() {
+        let backend = Arc::new(FakeBackend {
+            state: Mutex::new(BackendState::Stopped),
+            resolves: AtomicUsize::new(0),
+        });
+        let manager =
+            BackendManager::new(backend.clone(), Duration::from_secs(60), JoinMode::Transfer);
+        assert_eq!(manager.server_state().await, Some(ServerState::Sleeping));
+
+        *backend.state.lock().unwrap() = BackendState::Stopping;
+        assert_eq!(manager.server_state().await, Some(ServerState::Sleeping));
+
+        // starting the server shows up right away
+        *backend.state.lock().unwrap() = BackendState::Stopped;
+        let login_start = LoginStart {
+            username: "Notch".to_string(),
+            uuid: Uuid::from_u128(1),
+        };
+        let client_addr = "203.0.113.7:51234".parse().unwrap();
+        assert!(matches!(manager.on_join(&login_start, client_addr).await, Ok(None)));
+        assert_eq!(
+            manager.server_state().await,
+            Some(ServerState::Starting { eta: None })
+        );
+    }
+
+    #[tokio::test]
+    async fn caches_unreachable_status() {
+        let backend = Arc::new(FakeBackend {
+            state: Mutex::new(BackendState::Stopped),
+            resolves: AtomicUsize::new(0),
+        });
+        let manager =
+            BackendManager::new(backend.clone(), Duration::from_secs(60), JoinMode::Transfer);
+
+        assert!(manager.backend_status().await.is_none());
+        assert!(manager.backend_status().await.is_none());
+        assert_eq!(backend.resolves.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn not_ready_reason_uses_eta() {
+        let backend = Arc::new(FakeBackend {
+            state: Mutex::new(BackendState::Starting {
+                eta: Some(Duration::from_secs(150)),
+            }),
+            resolves: AtomicUsize::new(0),
+        });
+        let manager = BackendManager::new(backend.clone(), Duration::ZERO, JoinMode::Transfer);
+        assert_eq!(
+            manager.not_ready_reason().await.to_plain(),
+            "Server is starting, try again in ~3 minutes"
+        );
+
+        *backend.state.lock().unwrap() = BackendState::Starting { eta: None };
+        assert_eq!(
+            manager.not_ready_reason().await.to_plain(),
+            "Server is starting, try again in a few minutes"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::connection::ProfileProperty;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::io;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const MOJANG_SESSION_SERVER: &str = "https://sessionserver.mojang.com";
+
+// How long the session server gets to answer, so that an outage fails logins rather than
+// holding them.
+const SESSION_SERVER_TIMEOUT: u64 = 10;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GameProfile {
+    // The session server returns the UUID without dashes.
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub properties: Vec<ProfileProperty>,
+}
+
+impl GameProfile {
+    pub fn uuid(&self) -> io::Result<Uuid> {
+        Uuid::parse_str(&self.id).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid profile UUID: {}", self.id),
+            )
+        })
+    }
+}
+
+/// Client for the session server that verifies a player has joined with their Mojang account.
+#[derive(Clone)]
+pub struct SessionServer {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl SessionServer {
+    pub fn new(base_url: String) -> Self {
+        SessionServer {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(SESSION_SERVER_TIMEOUT))
+                .build()
+                .expect("Failed to build the session server client."),
+        }
+    }
+
+    /// Returns the player's profile if they have joined with the given server hash, or `None` if
+    /// the session server doesn't recognize the join.
+    pub async fn has_joined(
+        &self,
+        username: &str,
+        server_hash: &str,
+    ) -> io::Result<Option<GameProfile>> {
+        let url = format!("{}/session/minecraft/hasJoined", self.base_url);
+        let response = self
+            .client
+            .get(url)
+            .query(&[("username", username), ("serverId", server_hash)])
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+
+        // No content means the client never told the session server it was joining.
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status().map_err(io::Error::other)?;
+        let profile = response
+            .json::<GameProfile>()
+            .await
+            .map_err(io::Error::other)?;
+        Ok(Some(profile))
+    }
+}
+
+/// Computes the server hash sent to the session server: the SHA-1 digest of the server ID, shared
+/// secret and public key, formatted as a signed two's complement hex number like Java's
+/// `BigInteger.toString(16)`.
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_key);
+    let mut digest: Vec<u8> = hasher.finalize().to_vec();
+
+    let negative = digest[0] & 0x80 != 0;
+    if negative {
+        // two's complement: invert the bits and add one
+        let mut carry = true;
+        for byte in digest.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                let (sum, overflow) = byte.overflowing_add(1);
+                *byte = sum;
+                carry = overflow;
+            }
+        }
+    }
+
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let hex = hex.trim_start_matches('0');
+    if negative {
+        format!("-{}", hex)
+    } else {
+        hex.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn server_hash_digest() {
+        // test values from
+        // https://minecraft.wiki/w/Java_Edition_protocol/Encryption#Authentication
+        assert_eq!(
+            server_hash("Notch", &[], &[]),
+            "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48"
+        );
+        assert_eq!(
+            server_hash("jeb_", &[], &[]),
+            "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1"
+        );
+        assert_eq!(
+            server_hash("simon", &[], &[]),
+            "88e16a1019277b15d58faf0541e11910eb756f6"
+        );
+    }
+
+    // Serves a single HTTP request with the given status and body, returning the request line.
+    async fn stub_session_server(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let len = stream.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..len]).to_string();
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (base_url, handle)
+    }
+
+    #[tokio::test]
+    async fn has_joined() -> Result<(), io::Error> {
+        let (base_url, request) = stub_session_server(
+            "200 OK",
+            r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch","properties":[{"name":"textures","value":"dGV4dHVyZXM=","signature":"c2lnbmF0dXJl"}]}"#,
+        )
+        .await;
+
+        let session_server = SessionServer::new(base_url);
+        let profile = session_server
+            .has_joined("Notch", "-abc123")
+            .await?
+            .unwrap();
+        assert_eq!(profile.name, "Notch");
+        assert_eq!(profile.properties.len(), 1);
+        assert_eq!(profile.properties[0].name, "textures");
+        assert_eq!(
+            profile.properties[0].signature.as_deref(),
+            Some("c2lnbmF0dXJl")
+        );
+        assert_eq!(
+            profile.uuid()?,
+            Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+
+        let request = request.await.unwrap();
+        assert!(
+            request
+                .starts_with("GET /session/minecraft/hasJoined?username=Notch&serverId=-abc123 ")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn has_not_joined() -> Result<(), io::Error> {
+        let (base_url, _) = stub_session_server("204 No Content", "").await;
+
+        let session_server = SessionServer::new(base_url);
+        assert!(
+            session_server
+                .has_joined("Notch", "-abc123")
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+}